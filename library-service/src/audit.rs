@@ -0,0 +1,56 @@
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use std::future::{ready, Ready};
+use crate::auth::Identity;
+use crate::errors::ServiceError;
+use crate::telemetry::{RequestId, REQUEST_ID_HEADER};
+
+pub const ANONYMOUS_ACTOR: &str = "anonymous";
+
+//Who made a change and in which request, recorded with every change of a book in its history. The actor is
+//the subject of the bearer token, anonymous when the authentication is disabled.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor: String,
+    pub request_id: Option<String>,
+}
+
+impl Default for AuditContext {
+    fn default() -> Self {
+        AuditContext {
+            actor: ANONYMOUS_ACTOR.into(),
+            request_id: None,
+        }
+    }
+}
+
+impl AuditContext {
+    //The id comes from the RequestTracing middleware, or from the header when the middleware is not registered.
+    fn parse(req: &HttpRequest) -> Result<Self, ServiceError> {
+        let request_id = match (req.extensions().get::<RequestId>(), req.headers().get(REQUEST_ID_HEADER)) {
+            (Some(request_id), _) => Some(request_id.0.clone()),
+            (None, Some(value)) => match value.to_str() {
+                Ok(id) if RequestId::is_valid(id) => Some(id.to_string()),
+                _ => return Err(ServiceError::BadRequest("The X-Request-Id header is invalid.".into())),
+            },
+            (None, None) => None,
+        };
+        let actor = req
+            .extensions()
+            .get::<Identity>()
+            .map_or_else(|| ANONYMOUS_ACTOR.to_string(), |identity| identity.subject.clone());
+        Ok(AuditContext {
+            actor,
+            request_id,
+        })
+    }
+}
+
+impl FromRequest for AuditContext {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(AuditContext::parse(req))
+    }
+}
@@ -0,0 +1,338 @@
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use utoipa::ToSchema;
+use crate::dal::api_key::db_use_api_key;
+use crate::errors::ServiceError;
+use crate::model::api_key::{hash_api_key, API_KEY_HEADER};
+use crate::settings::AuthSettings;
+use crate::state::AppState;
+
+//The roles are ordered, every role can also do what the roles before it can.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Reader,
+    Librarian,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Librarian => "librarian",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Role> {
+        match role {
+            "reader" => Some(Role::Reader),
+            "librarian" => Some(Role::Librarian),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+//Claims read from the bearer token, the roles unknown to the service are ignored.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: u64,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+//Caller of the request, added to the request extensions by the Authentication middleware.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub subject: String,
+    pub roles: Vec<Role>,
+}
+
+impl Identity {
+    pub fn has_role(&self, required: Role) -> bool {
+        self.roles.iter().any(|role| *role >= required)
+    }
+}
+
+impl FromRequest for Identity {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(req
+            .extensions()
+            .get::<Identity>()
+            .cloned()
+            .ok_or_else(|| ServiceError::Unauthorized("A bearer token is required.".into())))
+    }
+}
+
+//Keys used to verify the bearer tokens, HS256 with a shared secret and RS256 with the public keys of a JWKS file.
+pub struct AuthConfig {
+    secret: Option<DecodingKey>,
+    jwks: Option<JwkSet>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    api_keys: bool,
+}
+
+impl AuthConfig {
+    pub fn new(secret: Option<&str>, jwks: Option<JwkSet>) -> Self {
+        AuthConfig {
+            secret: secret.map(|secret| DecodingKey::from_secret(secret.as_bytes())),
+            jwks,
+            issuer: None,
+            audience: None,
+            api_keys: true,
+        }
+    }
+
+    pub fn with_issuer(mut self, issuer: Option<String>) -> Self {
+        self.issuer = issuer;
+        self
+    }
+
+    pub fn with_audience(mut self, audience: Option<String>) -> Self {
+        self.audience = audience;
+        self
+    }
+
+    pub fn with_api_keys(mut self, api_keys: bool) -> Self {
+        self.api_keys = api_keys;
+        self
+    }
+
+    //None when the authentication is disabled, the settings must have been validated.
+    pub fn from_settings(settings: &AuthSettings) -> Result<Option<Self>, String> {
+        if settings.disabled {
+            return Ok(None);
+        }
+        let jwks = match &settings.jwks_file {
+            Some(path) => {
+                let file = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+                Some(serde_json::from_str::<JwkSet>(&file)
+                    .map_err(|err| format!("Invalid JWKS file {}: {}", path.display(), err))?)
+            }
+            None => None,
+        };
+        let secret = settings.jwt_secret.as_deref().filter(|secret| !secret.is_empty());
+        //There is no default secret, a token could be signed with it.
+        if secret.is_none() && jwks.is_none() {
+            return Err("auth.jwt_secret or auth.jwks_file is required, or auth.disabled = true.".into());
+        }
+        Ok(Some(AuthConfig::new(secret, jwks)
+            .with_issuer(settings.issuer.clone())
+            .with_audience(settings.audience.clone())))
+    }
+
+    pub fn authenticate(&self, token: &str) -> Result<Identity, ServiceError> {
+        let token_header = decode_header(token)
+            .map_err(|_| ServiceError::Unauthorized("The bearer token is malformed.".into()))?;
+        let key = match token_header.alg {
+            Algorithm::HS256 => self.secret.clone(),
+            Algorithm::RS256 => self.jwks.as_ref().and_then(|jwks| {
+                let jwk = match &token_header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None if jwks.keys.len() == 1 => jwks.keys.first(),
+                    None => None,
+                };
+                jwk.and_then(|jwk| DecodingKey::from_jwk(jwk).ok())
+            }),
+            _ => None,
+        }
+        .ok_or_else(|| ServiceError::Unauthorized("The bearer token is signed with an unknown key.".into()))?;
+
+        let mut validation = Validation::new(token_header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+        }
+        let claims = decode::<Claims>(token, &key, &validation)
+            .map_err(|_| ServiceError::Unauthorized("The bearer token is invalid or expired.".into()))?
+            .claims;
+        Ok(Identity {
+            subject: claims.sub,
+            roles: claims.roles.iter().filter_map(|role| Role::parse(role)).collect(),
+        })
+    }
+}
+
+//Key sent by the machine clients in the X-API-Key header instead of a bearer token.
+#[derive(Debug, Clone)]
+pub struct ApiKeyCredentials(pub String);
+
+impl FromRequest for ApiKeyCredentials {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|key| ApiKeyCredentials(key.trim().to_string()))
+            .filter(|credentials| !credentials.0.is_empty())
+            .ok_or_else(|| ServiceError::Unauthorized("The X-API-Key header is invalid.".into())))
+    }
+}
+
+//Validates the bearer token or the API key of the caller, the roles are checked by the RequireRole middleware of
+//the routes. The authentication is disabled when no AuthConfig is registered in the App.
+pub struct Authentication;
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            match authorize(&mut req).await {
+                Ok(identity) => {
+                    if let Some(identity) = identity {
+                        req.extensions_mut().insert(identity);
+                    }
+                    service.call(req).await.map(ServiceResponse::map_into_left_body)
+                }
+                Err(err) => Ok(req.error_response(err).map_into_right_body()),
+            }
+        })
+    }
+}
+
+async fn authorize(req: &mut ServiceRequest) -> Result<Option<Identity>, ServiceError> {
+    let config = match req.app_data::<web::Data<AuthConfig>>() {
+        Some(config) => config.clone(),
+        None => return Ok(None),
+    };
+    let identity = if req.headers().contains_key(API_KEY_HEADER) {
+        if !config.api_keys {
+            return Err(ServiceError::Unauthorized("The API keys are disabled.".into()));
+        }
+        let credentials = req.extract::<ApiKeyCredentials>().await?;
+        let app_state = req
+            .app_data::<web::Data<AppState>>()
+            .ok_or_else(|| ServiceError::ActixError("The application state is not registered.".into()))?;
+        let api_key = db_use_api_key(&hash_api_key(&credentials.0), &app_state.db).await?;
+        Identity {
+            subject: format!("api-key:{}", api_key.name),
+            roles: api_key.scopes.iter().filter_map(|scope| Role::parse(scope)).collect(),
+        }
+    } else {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or_else(|| ServiceError::Unauthorized("A bearer token or an API key is required.".into()))?;
+        config.authenticate(token)?
+    };
+    Ok(Some(identity))
+}
+
+//Role required by a route, checked against the Identity added by the Authentication middleware of its scope. The
+//role is bound to the route, not read from the path which can be written in several ways (/bul%6B is /bulk).
+pub struct RequireRole(pub Role);
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireRoleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware { service: Rc::new(service), role: self.0 }))
+    }
+}
+
+pub struct RequireRoleMiddleware<S> {
+    service: Rc<S>,
+    role: Role,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let role = self.role;
+        Box::pin(async move {
+            match check_role(&req, role) {
+                Ok(()) => service.call(req).await.map(ServiceResponse::map_into_left_body),
+                Err(err) => Ok(req.error_response(err).map_into_right_body()),
+            }
+        })
+    }
+}
+
+//Every role is allowed when the authentication is disabled.
+fn check_role(req: &ServiceRequest, role: Role) -> Result<(), ServiceError> {
+    if req.app_data::<web::Data<AuthConfig>>().is_none() {
+        return Ok(());
+    }
+    match req.extensions().get::<Identity>() {
+        Some(identity) if identity.has_role(role) => Ok(()),
+        Some(_) => Err(ServiceError::Forbidden(format!("The {} role is required.", role.as_str()))),
+        None => Err(ServiceError::Unauthorized("A bearer token or an API key is required.".into())),
+    }
+}
@@ -0,0 +1,223 @@
+use actix_cors::Cors;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::{Method, Uri};
+use serde::{Deserialize, Serialize};
+
+//The dev profile accepts every origin, method and header, it must not be used in production.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CorsProfile {
+    #[default]
+    Strict,
+    Dev,
+}
+
+//CORS policy of the service. The allowed origins are exact origins ("https://library.example.com"), patterns
+//with a wildcard subdomain ("https://*.example.com") or "*" for any origin.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    pub profile: CorsProfile,
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub exposed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age: Option<usize>,
+}
+
+//The defaults are the policy of the React development server.
+impl Default for CorsSettings {
+    fn default() -> Self {
+        CorsSettings {
+            profile: CorsProfile::Strict,
+            allowed_origins: vec!["http://localhost:3000".into()],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: [
+                "authorization",
+                "accept",
+                "content-type",
+                "if-match",
+                "if-none-match",
+                "x-request-id",
+                "x-api-key",
+            ]
+            .map(String::from)
+            .to_vec(),
+            exposed_headers: [
+                "etag",
+                "location",
+                "x-request-id",
+                "retry-after",
+                "ratelimit-limit",
+                "ratelimit-remaining",
+                "ratelimit-reset",
+                "deprecation",
+                "sunset",
+                "link",
+            ]
+            .map(String::from)
+            .to_vec(),
+            allow_credentials: false,
+            max_age: Some(3600),
+        }
+    }
+}
+
+impl CorsSettings {
+    //actix-cors only reports the invalid values when the server starts its workers, they are checked before.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.profile == CorsProfile::Dev {
+            return Ok(());
+        }
+        for origin in &self.allowed_origins {
+            if origin == "*" {
+                if self.allow_credentials {
+                    return Err("The credentials can not be allowed with the origin *.".into());
+                }
+                continue;
+            }
+            let uri = origin.replacen("*.", "", 1).parse::<Uri>().map_err(|_| format!("Invalid origin: {:?}", origin))?;
+            if uri.scheme().is_none() || uri.host().is_none() || uri.path() != "/" || origin.ends_with('/') {
+                return Err(format!("Invalid origin, it must be scheme://host[:port]: {:?}", origin));
+            }
+            if origin.matches('*').count() > 1 || (origin.contains('*') && !origin.contains("://*.")) {
+                return Err(format!("Invalid origin, the wildcard must be the first subdomain: {:?}", origin));
+            }
+        }
+        for method in &self.allowed_methods {
+            Method::from_bytes(method.as_bytes()).map_err(|_| format!("Invalid method: {:?}", method))?;
+        }
+        for header in self.allowed_headers.iter().chain(&self.exposed_headers) {
+            HeaderName::from_bytes(header.as_bytes()).map_err(|_| format!("Invalid header: {:?}", header))?;
+        }
+        Ok(())
+    }
+
+    //The settings must have been validated.
+    pub fn cors(&self) -> Cors {
+        if self.profile == CorsProfile::Dev {
+            return Cors::permissive();
+        }
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.iter().map(String::as_str))
+            .allowed_headers(self.allowed_headers.iter().map(String::as_str))
+            .expose_headers(self.exposed_headers.iter().map(String::as_str))
+            .max_age(self.max_age);
+        if self.allowed_origins.iter().any(|origin| origin == "*") {
+            cors = cors.allow_any_origin();
+        } else {
+            let origins = self.allowed_origins.clone();
+            cors = cors.allowed_origin_fn(move |origin, _req| is_allowed_origin(&origins, origin));
+        }
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
+
+fn is_allowed_origin(allowed_origins: &[String], origin: &HeaderValue) -> bool {
+    let origin = match origin.to_str() {
+        Ok(origin) => origin.to_ascii_lowercase(),
+        Err(_) => return false,
+    };
+    allowed_origins.iter().any(|allowed| {
+        let allowed = allowed.to_ascii_lowercase();
+        match allowed.split_once("://*.") {
+            //The wildcard stands for one or more subdomains, the scheme and the port must be the same.
+            Some((scheme, domain)) => origin
+                .strip_prefix(&format!("{}://", scheme))
+                .and_then(|host| host.strip_suffix(&format!(".{}", domain)))
+                .is_some_and(|subdomains| {
+                    !subdomains.is_empty()
+                        && subdomains.split('.').all(|label| {
+                            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                        })
+                }),
+            None => origin == allowed,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use std::collections::HashMap;
+    use crate::routes::general_routes;
+    use crate::settings::Settings;
+    use crate::test_utils::test_state;
+
+    #[actix_rt::test]
+    async fn test_cors_policy() {
+        let shared_data = test_state().await;
+        let cors_settings = CorsSettings {
+            allowed_origins: vec!["https://library.example.org".into(), "https://*.example.com".into()],
+            allow_credentials: true,
+            ..CorsSettings::default()
+        };
+        cors_settings.validate().unwrap();
+        let app = test::init_service(App::new()
+            .wrap(cors_settings.cors())
+            .app_data(shared_data.clone())
+            .configure(general_routes)).await;
+
+        for origin in ["https://library.example.org", "https://ui.example.com", "https://a.b.example.com"] {
+            let request = test::TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/probe")
+                .insert_header((header::ORIGIN, origin))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            assert_eq!(http_response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), origin);
+            assert_eq!(http_response.headers().get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+        }
+        for origin in ["https://example.com", "http://ui.example.com", "https://ui.example.com.evil.org", "http://localhost:3000"] {
+            let request = test::TestRequest::get()
+                .uri("/probe")
+                .insert_header((header::ORIGIN, origin))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert!(http_response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        }
+
+        let dev_settings = CorsSettings {
+            profile: CorsProfile::Dev,
+            ..CorsSettings::default()
+        };
+        let app = test::init_service(App::new()
+            .wrap(dev_settings.cors())
+            .app_data(shared_data)
+            .configure(general_routes)).await;
+        let request = test::TestRequest::get()
+            .uri("/probe")
+            .insert_header((header::ORIGIN, "http://192.168.1.20:8080"))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        assert_eq!(http_response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "http://192.168.1.20:8080");
+
+        let invalid_settings = [
+            ("CORS_ALLOWED_ORIGINS", "https://example.com/books"),
+            ("CORS_ALLOWED_ORIGINS", "https://ui.*.example.com"),
+            ("CORS_ALLOWED_METHODS", "GET,NOT A METHOD"),
+            ("CORS_ALLOWED_HEADERS", "x-valid,in valid"),
+        ];
+        for (variable, value) in invalid_settings {
+            let vars = HashMap::from([(variable.to_string(), value.to_string())]);
+            let settings = Settings::load_from(None, vars, &[]).unwrap();
+            assert!(settings.cors.validate().is_err(), "{} = {} must be rejected", variable, value);
+        }
+        let any_origin_with_credentials = CorsSettings {
+            allowed_origins: vec!["*".into()],
+            allow_credentials: true,
+            ..CorsSettings::default()
+        };
+        assert!(any_origin_with_credentials.validate().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::Utc;
+use sqlx::postgres::PgPool;
+use crate::errors::ServiceError;
+use crate::metrics::QueryTimer;
+use crate::model::api_key::{ApiKey, NewApiKey};
+use tracing::instrument;
+
+#[instrument(skip_all)]
+pub async fn db_create_api_key(pool: &PgPool, api_key: NewApiKey, key_hash: &str, prefix: &str) -> Result<ApiKey, ServiceError> {
+    let _timer = QueryTimer::start("db_create_api_key");
+    let scopes: Vec<String> = api_key.scopes.iter().map(|scope| scope.as_str().to_string()).collect();
+    let api_key = sqlx::query_as!(ApiKey, "INSERT INTO public.api_keys(name, prefix, key_hash, scopes, expires_at, record_timestamp) VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, prefix, scopes, expires_at, revoked_at, last_used_at, request_count, record_timestamp AS created_at",
+        api_key.name,
+        prefix,
+        key_hash,
+        &scopes[..],
+        api_key.expires_at,
+        Utc::now().naive_utc())
+        .fetch_one(pool)
+        .await?;
+    Ok(api_key)
+}
+
+#[instrument(skip_all)]
+pub async fn db_read_api_keys(pool: &PgPool) -> Result<Vec<ApiKey>, ServiceError> {
+    let _timer = QueryTimer::start("db_read_api_keys");
+    let api_keys = sqlx::query_as!(ApiKey, "SELECT id, name, prefix, scopes, expires_at, revoked_at, last_used_at, request_count, record_timestamp AS created_at
+        FROM public.api_keys
+        ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    Ok(api_keys)
+}
+
+//The old key stops working at once, the usage of the key is kept.
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_rotate_api_key(id: i32, key_hash: &str, prefix: &str, pool: &PgPool) -> Result<ApiKey, ServiceError> {
+    let _timer = QueryTimer::start("db_rotate_api_key");
+    sqlx::query_as!(ApiKey, "UPDATE public.api_keys SET key_hash = $2, prefix = $3 WHERE id = $1 AND revoked_at IS NULL
+        RETURNING id, name, prefix, scopes, expires_at, revoked_at, last_used_at, request_count, record_timestamp AS created_at",
+        id,
+        key_hash,
+        prefix)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("API key not found or revoked.".into()))
+}
+
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_revoke_api_key(id: i32, pool: &PgPool) -> Result<ApiKey, ServiceError> {
+    let _timer = QueryTimer::start("db_revoke_api_key");
+    sqlx::query_as!(ApiKey, "UPDATE public.api_keys SET revoked_at = $2 WHERE id = $1 AND revoked_at IS NULL
+        RETURNING id, name, prefix, scopes, expires_at, revoked_at, last_used_at, request_count, record_timestamp AS created_at",
+        id,
+        Utc::now().naive_utc())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("API key not found or revoked.".into()))
+}
+
+//Finds the active key with the hash and counts the request in its usage.
+#[instrument(skip_all)]
+pub async fn db_use_api_key(key_hash: &str, pool: &PgPool) -> Result<ApiKey, ServiceError> {
+    let _timer = QueryTimer::start("db_use_api_key");
+    let now = Utc::now().naive_utc();
+    sqlx::query_as!(ApiKey, "UPDATE public.api_keys SET last_used_at = $2, request_count = request_count + 1
+        WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > $2)
+        RETURNING id, name, prefix, scopes, expires_at, revoked_at, last_used_at, request_count, record_timestamp AS created_at",
+        key_hash,
+        now)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ServiceError::Unauthorized("The API key is invalid, expired or revoked.".into()))
+}
@@ -1,119 +1,501 @@
 use actix_web::HttpResponse;
-use chrono::Utc;
-use sqlx::postgres::PgPool;
-use crate::model::book::Book;
+use async_stream::try_stream;
+use chrono::{Duration, Utc};
+use futures_util::{Stream, TryStreamExt};
+use sqlx::postgres::{PgConnection, PgPool, Postgres};
+use sqlx::QueryBuilder;
+use std::collections::{HashMap, HashSet};
+use crate::model::book::{
+    Book, BookPage, BookPatch, BookQuery, BookSearchQuery, BookSearchResult, BulkBook, BulkMode, BulkOptions,
+    BulkRowResult, BulkRowStatus, ConflictPolicy, NewBook, PurgeReport, SortOrder, TrashPage, TrashQuery, TrashedBook,
+    UpdateBook, BULK_CHUNK_SIZE,
+};
+use crate::audit::AuditContext;
+use crate::dal::history::db_append_history;
 use crate::errors::ServiceError;
+use crate::metrics::QueryTimer;
+use crate::model::history::{AuditAction, HistoryEntry};
+use tracing::instrument;
 
-pub async fn db_add_book(pool: &PgPool, book: Book) -> Result<HttpResponse, ServiceError> {
-    let query_result = sqlx::query!("INSERT INTO public.books(id, title, author) VALUES ( $1, $2, $3)",
-        book.id,
+#[instrument(skip_all)]
+pub async fn db_add_book(pool: &PgPool, book: NewBook, audit: &AuditContext) -> Result<Book, ServiceError> {
+    let _timer = QueryTimer::start("db_add_book");
+    let mut transaction = pool.begin().await?;
+    let book = sqlx::query_as!(Book, "INSERT INTO public.books(title, author) VALUES ($1, $2) RETURNING id, title, author, record_timestamp AS posted_time, version",
         book.title,
         book.author)
-        .execute(pool)
+        .fetch_one(&mut *transaction)
         .await?;
+    db_append_history(&mut transaction, &[HistoryEntry::new(AuditAction::Create, None, &book)], audit).await?;
+    transaction.commit().await?;
+    Ok(book)
+}
 
-    if query_result.rows_affected() > 0 {
-        Ok(HttpResponse::Ok().json("Book inserted"))
-    } else {
-        Err(ServiceError::NotFound("Book not inserted.".into(), ))
+//Applies the rows of a bulk insert in one transaction, in chunks of BULK_CHUNK_SIZE rows. The rows keep the
+//index they had in the request and the first element of the result is false when the transaction was rolled back.
+#[instrument(skip_all)]
+pub async fn db_bulk_insert(pool: &PgPool, rows: Vec<(usize, BulkBook)>, options: BulkOptions, audit: &AuditContext) -> Result<(bool, Vec<BulkRowResult>), ServiceError> {
+    let _timer = QueryTimer::start("db_bulk_insert");
+    let mut transaction = pool.begin().await?;
+    let mut results: Vec<BulkRowResult> = Vec::with_capacity(rows.len());
+    let mut explicit_ids = false;
+
+    for chunk in rows.chunks(BULK_CHUNK_SIZE) {
+        let chunk_ids: Vec<i32> = chunk.iter().filter_map(|(_, book)| book.id).collect();
+        let existing_rows = sqlx::query!(r#"SELECT id, title, author, record_timestamp, version, deleted_at IS NOT NULL AS "deleted!" FROM public.books WHERE id = ANY($1) FOR UPDATE"#,
+            &chunk_ids[..])
+            .fetch_all(&mut *transaction)
+            .await?;
+        let trashed_ids: Vec<i32> = existing_rows.iter().filter(|row| row.deleted).map(|row| row.id).collect();
+        let existing_ids: Vec<i32> = existing_rows.iter().map(|row| row.id).collect();
+        let existing_books: HashMap<i32, Book> = existing_rows
+            .into_iter()
+            .map(|row| (row.id, Book {
+                id: row.id,
+                title: row.title,
+                author: row.author,
+                posted_time: row.record_timestamp,
+                version: row.version,
+            }))
+            .collect();
+        let new_books_count = chunk.iter().filter(|(_, book)| book.id.is_none()).count() as i32;
+        let mut new_ids = sqlx::query_scalar!(r#"SELECT nextval(pg_get_serial_sequence('public.books', 'id'))::int4 AS "id!" FROM generate_series(1, $1)"#,
+            new_books_count)
+            .fetch_all(&mut *transaction)
+            .await?
+            .into_iter();
+
+        let mut insert_id: Vec<i32> = Vec::new();
+        let mut insert_title: Vec<String> = Vec::new();
+        let mut insert_author: Vec<String> = Vec::new();
+        let mut update_id: Vec<i32> = Vec::new();
+        let mut update_title: Vec<String> = Vec::new();
+        let mut update_author: Vec<String> = Vec::new();
+        let chunk_start = results.len();
+        for (index, book) in chunk {
+            let (id, status) = match book.id {
+                //The trashed books are not updated, they have to be restored first.
+                Some(id) if trashed_ids.contains(&id) => {
+                    results.push(BulkRowResult {
+                        index: *index,
+                        status: BulkRowStatus::Conflict,
+                        id: Some(id),
+                        reason: Some("A book with this id is in the trash.".into()),
+                        line: None,
+                    });
+                    continue;
+                }
+                Some(id) if existing_ids.contains(&id) => match options.on_conflict {
+                    ConflictPolicy::Update => {
+                        update_id.push(id);
+                        update_title.push(book.title.clone());
+                        update_author.push(book.author.clone());
+                        (id, BulkRowStatus::Updated)
+                    }
+                    ConflictPolicy::Error => {
+                        results.push(BulkRowResult {
+                            index: *index,
+                            status: BulkRowStatus::Conflict,
+                            id: Some(id),
+                            reason: Some("A book with this id already exists.".into()),
+                            line: None,
+                        });
+                        continue;
+                    }
+                },
+                Some(id) => {
+                    explicit_ids = true;
+                    (id, BulkRowStatus::Created)
+                }
+                None => match new_ids.next() {
+                    Some(id) => (id, BulkRowStatus::Created),
+                    None => return Err(ServiceError::DBError("The sequence of the table books returned too few ids.".into())),
+                },
+            };
+            if status == BulkRowStatus::Created {
+                insert_id.push(id);
+                insert_title.push(book.title.clone());
+                insert_author.push(book.author.clone());
+            }
+            results.push(BulkRowResult {
+                index: *index,
+                status,
+                id: Some(id),
+                reason: None,
+                line: None,
+            });
+        }
+
+        let created_books = sqlx::query_as!(Book, "INSERT INTO public.books(id, title, author) SELECT * FROM UNNEST ($1::int4[], $2::text[], $3::text[]) ON CONFLICT (id) DO NOTHING RETURNING id, title, author, record_timestamp AS posted_time, version",
+            &insert_id[..],
+            &insert_title[..],
+            &insert_author[..])
+            .fetch_all(&mut *transaction)
+            .await?;
+        let updated_books = sqlx::query_as!(Book, "UPDATE public.books SET title = u.title, author = u.author, record_timestamp = $4, version = version + 1 FROM UNNEST ($1::int4[], $2::text[], $3::text[]) AS u(id, title, author) WHERE public.books.id = u.id RETURNING public.books.id, public.books.title, public.books.author, public.books.record_timestamp AS posted_time, public.books.version",
+            &update_id[..],
+            &update_title[..],
+            &update_author[..],
+            Utc::now().naive_utc())
+            .fetch_all(&mut *transaction)
+            .await?;
+        //The book of a row may have been created by a concurrent request since it was read, only this row is a
+        //conflict.
+        let mut created_ids: HashSet<i32> = created_books.iter().map(|book| book.id).collect();
+        for result in results[chunk_start..].iter_mut() {
+            if result.status == BulkRowStatus::Created && !result.id.is_some_and(|id| created_ids.remove(&id)) {
+                result.status = BulkRowStatus::Conflict;
+                result.reason = Some("A book with this id already exists.".into());
+            }
+        }
+        let history: Vec<HistoryEntry> = created_books
+            .iter()
+            .map(|book| HistoryEntry::new(AuditAction::Create, None, book))
+            .chain(updated_books.iter().map(|book| HistoryEntry::new(AuditAction::Update, existing_books.get(&book.id), book)))
+            .collect();
+        db_append_history(&mut transaction, &history, audit).await?;
+    }
+
+    let has_conflicts = results.iter().any(|result| result.status == BulkRowStatus::Conflict);
+    if options.mode == BulkMode::AllOrNothing && has_conflicts {
+        transaction.rollback().await?;
+        results
+            .iter_mut()
+            .filter(|result| result.status != BulkRowStatus::Conflict)
+            .for_each(|result| {
+                result.status = BulkRowStatus::RolledBack;
+                result.id = None;
+            });
+        return Ok((false, results));
+    }
+
+    //The books created with an explicit id must not be handed out again by the sequence.
+    if explicit_ids {
+        sqlx::query!("SELECT setval(pg_get_serial_sequence('public.books', 'id'), GREATEST(MAX(id), COALESCE(pg_sequence_last_value(pg_get_serial_sequence('public.books', 'id')::regclass), 1))) FROM public.books")
+            .fetch_one(&mut *transaction)
+            .await?;
     }
+    transaction.commit().await?;
+    Ok((true, results))
 }
 
-pub async fn db_bulk_insert(pool: &PgPool, rows: Vec<Book>) -> Result<HttpResponse, ServiceError> {
-    let mut book_id: Vec<i32> = Vec::new();
-    let mut book_title: Vec<String> = Vec::new();
-    let mut book_author: Vec<String> = Vec::new();
-    rows.into_iter().for_each(|book| {
-        book_id.push(book.id);
-        book_title.push(book.title.into());
-        book_author.push(book.author.into());
-    });
-
-    let query_result = sqlx::query!("INSERT INTO public.books(id, title, author) SELECT * FROM UNNEST ($1::int4[], $2::text[], $3::text[])",
-        &book_id[..],
-        &book_title[..],
-        &book_author[..]
-    )
-        .execute(pool)
+#[instrument(skip_all)]
+pub async fn db_read_books(pool: &PgPool, query: &BookQuery) -> Result<BookPage, ServiceError> {
+    let _timer = QueryTimer::start("db_read_books");
+    let limit = query.limit();
+    let offset = query.offset();
+
+    //The page after a purged book could not be found, the listing would look finished.
+    if let Some(cursor) = query.cursor {
+        let exists = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM public.books WHERE id = $1) AS "exists!""#, cursor)
+            .fetch_one(pool)
+            .await?;
+        if !exists {
+            return Err(ServiceError::BadRequest(format!("The cursor {} is not a book anymore, the listing has to start again.", cursor)));
+        }
+    }
+
+    let mut count_builder = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM public.books");
+    push_book_filters(&mut count_builder, query);
+    let (total,): (i64,) = count_builder
+        .build_query_as()
+        .fetch_one(pool)
         .await?;
-    if query_result.rows_affected() > 0 {
-        Ok(HttpResponse::Ok().json("Books inserted"))
+
+    let sort_column = query.sort.column();
+    let (direction, comparison) = match query.order {
+        SortOrder::Asc => ("ASC", ">"),
+        SortOrder::Desc => ("DESC", "<"),
+    };
+    let mut rows_builder = QueryBuilder::<Postgres>::new(
+        "SELECT id, title, author, record_timestamp AS posted_time, version FROM public.books");
+    push_book_filters(&mut rows_builder, query);
+    if let Some(cursor) = query.cursor {
+        rows_builder
+            .push(format!(" AND ({}, id) {} (SELECT {}, id FROM public.books WHERE id = ", sort_column, comparison, sort_column))
+            .push_bind(cursor)
+            .push(")");
+    }
+    rows_builder
+        .push(format!(" ORDER BY {} {}, id {}", sort_column, direction, direction))
+        .push(" LIMIT ")
+        .push_bind(limit + 1)
+        .push(" OFFSET ")
+        .push_bind(offset);
+    let mut items: Vec<Book> = rows_builder
+        .build_query_as()
+        .fetch_all(pool)
+        .await?;
+
+    //One extra row is read to find out if there is a next page.
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|book| book.id)
     } else {
-        Err(ServiceError::NotFound("Books not inserted.".into(), ))
+        None
+    };
+
+    Ok(BookPage {
+        items,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    })
+}
+
+fn push_book_filters(builder: &mut QueryBuilder<Postgres>, query: &BookQuery) {
+    builder.push(" WHERE deleted_at IS NULL");
+    if let Some(author) = &query.author {
+        builder.push(" AND author ILIKE ").push_bind(like_pattern(author));
+    }
+    if let Some(title) = &query.title {
+        builder.push(" AND title ILIKE ").push_bind(like_pattern(title));
     }
 }
 
-pub async fn db_read_books(pool: &PgPool) -> Result<Vec<Book>, ServiceError> {
-    let query_rows = sqlx::query!("SELECT id, title, author, record_timestamp FROM public.books;")
+fn like_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+//The connection is acquired before the stream is returned so an unreachable database fails the request
+//instead of the body. The books are read one by one from the cursor, the table is never loaded into memory.
+#[instrument(skip_all)]
+pub async fn db_stream_books(pool: &PgPool) -> Result<impl Stream<Item = Result<Book, ServiceError>>, ServiceError> {
+    let timer = QueryTimer::start("db_stream_books");
+    let mut connection = pool.acquire().await?;
+    Ok(try_stream! {
+        let _timer = timer;
+        let mut rows = sqlx::query_as!(Book, "SELECT id, title, author, record_timestamp AS posted_time, version FROM public.books WHERE deleted_at IS NULL ORDER BY id")
+            .fetch(&mut *connection);
+        while let Some(book) = rows.try_next().await? {
+            yield book;
+        }
+    })
+}
+
+#[instrument(skip_all)]
+pub async fn db_search_books(pool: &PgPool, query: &BookSearchQuery) -> Result<Vec<BookSearchResult>, ServiceError> {
+    let _timer = QueryTimer::start("db_search_books");
+    let query_rows = sqlx::query!(r#"SELECT id, title, author, record_timestamp, version,
+            ts_rank(search, query) AS "rank!",
+            ts_headline('english', html_escape(title), query, 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS "title_highlight!",
+            ts_headline('english', html_escape(author), query, 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS "author_highlight!"
+        FROM public.books, websearch_to_tsquery('english', $1) AS query
+        WHERE search @@ query AND deleted_at IS NULL
+        ORDER BY ts_rank(search, query) DESC, id
+        LIMIT $2"#,
+        query.q,
+        query.limit())
         .fetch_all(pool)
-        .await
-        .unwrap();
+        .await?;
 
-    let query_result: Vec<Book> = query_rows
-        .iter()
-        .map(|row| Book {
-            id: row.id,
-            title: row.title.clone(),
-            author: row.author.clone(),
-            posted_time: Some(chrono::NaiveDateTime::from(row.record_timestamp.unwrap())),
+    Ok(query_rows
+        .into_iter()
+        .map(|row| BookSearchResult {
+            book: Book {
+                id: row.id,
+                title: row.title,
+                author: row.author,
+                posted_time: row.record_timestamp,
+                version: row.version,
+            },
+            rank: row.rank,
+            title_highlight: row.title_highlight,
+            author_highlight: row.author_highlight,
         })
-        .collect();
-    if query_result.len() > 0 {
-        Ok(query_result)
-    } else {
-        Err(ServiceError::NotFound("The library has no books.".into(), ))
-    }
+        .collect())
 }
 
+#[instrument(skip_all, fields(id = id))]
 pub async fn db_read_book_by_id(id: i32, pool: &PgPool) -> Result<Book, ServiceError> {
-    let query_row = sqlx::query!("SELECT id, title, author, record_timestamp FROM public.books WHERE id = $1",id)
-        .fetch_all(pool)
-        .await
-        .unwrap();
-
-    if query_row.len() > 0 {
-        Ok(Book {
-            id: query_row[0].id,
-            title: query_row[0].title.clone(),
-            author: query_row[0].author.clone(),
-            posted_time: Some(chrono::NaiveDateTime::from(query_row[0].record_timestamp.unwrap())),
-        })
-    } else {
-        Err(ServiceError::NotFound("Book not found.".into(), ))
+    let _timer = QueryTimer::start("db_read_book_by_id");
+    let query_row = sqlx::query!("SELECT id, title, author, record_timestamp, version FROM public.books WHERE id = $1 AND deleted_at IS NULL",id)
+        .fetch_optional(pool)
+        .await?;
+
+    match query_row {
+        Some(row) => Ok(Book {
+            id: row.id,
+            title: row.title,
+            author: row.author,
+            posted_time: row.record_timestamp,
+            version: row.version,
+        }),
+        None => Err(ServiceError::NotFound("Book not found.".into(), )),
     }
 }
 
-pub async fn db_update_book_by_id(id: i32, updated_book: Book, pool: &PgPool) -> Result<Book, ServiceError> {
-    let timestamp = Some(Utc::now().naive_utc());
-    let query_result = sqlx::query!("UPDATE books SET title= $2, author= $3, record_timestamp = $4 WHERE id = $1;",
+//The expected versions come from the If-Match header, None when the update is unconditional.
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_update_book_by_id(id: i32, updated_book: UpdateBook, expected_versions: Option<&[i32]>, audit: &AuditContext, pool: &PgPool) -> Result<Book, ServiceError> {
+    let _timer = QueryTimer::start("db_update_book_by_id");
+    let mut transaction = pool.begin().await?;
+    let before = db_lock_book(&mut transaction, id, false, expected_versions).await?;
+    let book = sqlx::query_as!(Book, "UPDATE public.books SET title = $2, author = $3, record_timestamp = $4, version = version + 1 WHERE id = $1 RETURNING id, title, author, record_timestamp AS posted_time, version",
         id,
         updated_book.title,
         updated_book.author,
-        timestamp)
-        .execute(pool)
-        .await
-        .unwrap();
-
-    if query_result.rows_affected() > 0 {
-        Ok(Book {
-            id: updated_book.id,
-            title: updated_book.title.clone(),
-            author: updated_book.author.clone(),
-            posted_time: timestamp,
-        })
-    } else {
-        Err(ServiceError::NotFound("Book not updated.".into(), ))
-    }
+        Utc::now().naive_utc())
+        .fetch_one(&mut *transaction)
+        .await?;
+    db_append_history(&mut transaction, &[HistoryEntry::new(AuditAction::Update, Some(&before), &book)], audit).await?;
+    transaction.commit().await?;
+    Ok(book)
 }
 
-pub async fn db_delete_book_by_id(id: i32, pool: &PgPool) -> Result<HttpResponse, ServiceError> {
-    let query_result = sqlx::query!("DELETE FROM books WHERE id = $1;", id)
-        .execute(pool)
-        .await
-        .unwrap();
-    if query_result.rows_affected() > 0 {
-        Ok(HttpResponse::Ok().json("Book deleted."))
-    } else {
-        Err(ServiceError::NotFound("Book not updated.".into(), ))
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_patch_book_by_id(id: i32, patch: BookPatch, expected_versions: Option<&[i32]>, audit: &AuditContext, pool: &PgPool) -> Result<Book, ServiceError> {
+    let _timer = QueryTimer::start("db_patch_book_by_id");
+    let mut transaction = pool.begin().await?;
+    let before = db_lock_book(&mut transaction, id, false, expected_versions).await?;
+    let book = sqlx::query_as!(Book, "UPDATE public.books SET title = COALESCE($2, title), author = COALESCE($3, author), record_timestamp = $4, version = version + 1 WHERE id = $1 RETURNING id, title, author, record_timestamp AS posted_time, version",
+        id,
+        patch.title,
+        patch.author,
+        Utc::now().naive_utc())
+        .fetch_one(&mut *transaction)
+        .await?;
+    db_append_history(&mut transaction, &[HistoryEntry::new(AuditAction::Update, Some(&before), &book)], audit).await?;
+    transaction.commit().await?;
+    Ok(book)
+}
+
+//The book is moved to the trash, it is only removed from the table by db_purge_trash.
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_delete_book_by_id(id: i32, expected_versions: Option<&[i32]>, audit: &AuditContext, pool: &PgPool) -> Result<HttpResponse, ServiceError> {
+    let _timer = QueryTimer::start("db_delete_book_by_id");
+    let mut transaction = pool.begin().await?;
+    let before = db_lock_book(&mut transaction, id, false, expected_versions).await?;
+    let book = sqlx::query_as!(Book, "UPDATE public.books SET deleted_at = $2, version = version + 1 WHERE id = $1 RETURNING id, title, author, record_timestamp AS posted_time, version",
+        id,
+        Utc::now().naive_utc())
+        .fetch_one(&mut *transaction)
+        .await?;
+    db_append_history(&mut transaction, &[HistoryEntry::new(AuditAction::Delete, Some(&before), &book)], audit).await?;
+    transaction.commit().await?;
+    Ok(HttpResponse::Ok().json("Book deleted."))
+}
+
+#[instrument(skip_all)]
+pub async fn db_read_trash(pool: &PgPool, query: &TrashQuery) -> Result<TrashPage, ServiceError> {
+    let _timer = QueryTimer::start("db_read_trash");
+    let limit = query.limit();
+    let offset = query.offset();
+    let total = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "total!" FROM public.books WHERE deleted_at IS NOT NULL"#)
+        .fetch_one(pool)
+        .await?;
+    let query_rows = sqlx::query!(r#"SELECT id, title, author, record_timestamp, version, deleted_at AS "deleted_at!"
+        FROM public.books
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC, id
+        LIMIT $1 OFFSET $2"#,
+        limit,
+        offset)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(TrashPage {
+        items: query_rows
+            .into_iter()
+            .map(|row| TrashedBook {
+                book: Book {
+                    id: row.id,
+                    title: row.title,
+                    author: row.author,
+                    posted_time: row.record_timestamp,
+                    version: row.version,
+                },
+                deleted_at: row.deleted_at,
+            })
+            .collect(),
+        total,
+        limit,
+        offset,
+    })
+}
+
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_restore_book_by_id(id: i32, expected_versions: Option<&[i32]>, audit: &AuditContext, pool: &PgPool) -> Result<Book, ServiceError> {
+    let _timer = QueryTimer::start("db_restore_book_by_id");
+    let mut transaction = pool.begin().await?;
+    let before = db_lock_book(&mut transaction, id, true, expected_versions).await?;
+    let book = sqlx::query_as!(Book, "UPDATE public.books SET deleted_at = NULL, version = version + 1 WHERE id = $1 RETURNING id, title, author, record_timestamp AS posted_time, version",
+        id)
+        .fetch_one(&mut *transaction)
+        .await?;
+    db_append_history(&mut transaction, &[HistoryEntry::new(AuditAction::Restore, Some(&before), &book)], audit).await?;
+    transaction.commit().await?;
+    Ok(book)
+}
+
+//Brings back the title and author of the book after a past revision of its history.
+#[instrument(skip_all, fields(id = id))]
+pub async fn db_revert_book_to_revision(id: i32, revision: i64, expected_versions: Option<&[i32]>, audit: &AuditContext, pool: &PgPool) -> Result<Book, ServiceError> {
+    let _timer = QueryTimer::start("db_revert_book_to_revision");
+    let mut transaction = pool.begin().await?;
+    let before = db_lock_book(&mut transaction, id, false, expected_versions).await?;
+    let snapshot = sqlx::query_scalar!("SELECT after FROM public.book_history WHERE id = $1 AND book_id = $2",
+        revision,
+        id)
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Revision not found.".into()))?
+        .ok_or_else(|| ServiceError::BadRequest("The revision has no snapshot of the book to revert to.".into()))?;
+    let revision_book: Book = serde_json::from_value(snapshot)
+        .map_err(|err| ServiceError::DBError(format!("Invalid snapshot in revision {}: {}", revision, err)))?;
+    let book = sqlx::query_as!(Book, "UPDATE public.books SET title = $2, author = $3, record_timestamp = $4, version = version + 1 WHERE id = $1 RETURNING id, title, author, record_timestamp AS posted_time, version",
+        id,
+        revision_book.title,
+        revision_book.author,
+        Utc::now().naive_utc())
+        .fetch_one(&mut *transaction)
+        .await?;
+    db_append_history(&mut transaction, &[HistoryEntry::new(AuditAction::Revert, Some(&before), &book)], audit).await?;
+    transaction.commit().await?;
+    Ok(book)
+}
+
+//Removes for good the books that have been in the trash for longer than the retention.
+#[instrument(skip_all)]
+pub async fn db_purge_trash(retention_days: i64, audit: &AuditContext, pool: &PgPool) -> Result<PurgeReport, ServiceError> {
+    let _timer = QueryTimer::start("db_purge_trash");
+    let cutoff = Utc::now().naive_utc() - Duration::days(retention_days);
+    let mut transaction = pool.begin().await?;
+    let purged_books = sqlx::query_as!(Book, "DELETE FROM public.books WHERE deleted_at IS NOT NULL AND deleted_at < $1 RETURNING id, title, author, record_timestamp AS posted_time, version",
+        cutoff)
+        .fetch_all(&mut *transaction)
+        .await?;
+    let history: Vec<HistoryEntry> = purged_books.iter().map(HistoryEntry::purged).collect();
+    db_append_history(&mut transaction, &history, audit).await?;
+    transaction.commit().await?;
+
+    Ok(PurgeReport {
+        purged: purged_books.len() as u64,
+        retention_days,
+    })
+}
+
+//Locks the book for the rest of the transaction, trashed tells if the book is looked for in the trash.
+//The expected versions come from the If-Match header, None when the change is unconditional.
+#[instrument(skip_all, fields(id = id))]
+async fn db_lock_book(connection: &mut PgConnection, id: i32, trashed: bool, expected_versions: Option<&[i32]>) -> Result<Book, ServiceError> {
+    let book = sqlx::query_as!(Book, "SELECT id, title, author, record_timestamp AS posted_time, version FROM public.books WHERE id = $1 AND (deleted_at IS NOT NULL) = $2 FOR UPDATE",
+        id,
+        trashed)
+        .fetch_optional(connection)
+        .await?;
+
+    match book {
+        Some(book) if expected_versions.is_some_and(|versions| !versions.contains(&book.version)) => {
+            Err(ServiceError::PreconditionFailed("The book was changed by another request.".into()))
+        }
+        Some(book) => Ok(book),
+        None if trashed => Err(ServiceError::NotFound("Book not found in the trash.".into(), )),
+        None => Err(ServiceError::NotFound("Book not found.".into(), )),
     }
-}
\ No newline at end of file
+}
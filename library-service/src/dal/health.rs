@@ -0,0 +1,13 @@
+use sqlx::postgres::PgPool;
+use crate::errors::ServiceError;
+use crate::metrics::QueryTimer;
+use tracing::instrument;
+
+#[instrument(skip_all)]
+pub async fn db_ping(pool: &PgPool) -> Result<(), ServiceError> {
+    let _timer = QueryTimer::start("db_ping");
+    sqlx::query_scalar!("SELECT 1 AS \"one!\"")
+        .fetch_one(pool)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use chrono::Utc;
+use sqlx::postgres::{PgConnection, PgPool};
+use crate::audit::AuditContext;
+use crate::errors::ServiceError;
+use crate::metrics::QueryTimer;
+use crate::model::history::{BookRevision, HistoryEntry};
+use tracing::instrument;
+
+//Called with the transaction of the change so the history and the books are always committed together.
+#[instrument(skip_all)]
+pub async fn db_append_history(connection: &mut PgConnection, entries: &[HistoryEntry], audit: &AuditContext) -> Result<(), ServiceError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let book_ids: Vec<i32> = entries.iter().map(|entry| entry.book_id).collect();
+    let actions: Vec<String> = entries.iter().map(|entry| entry.action.as_str().to_string()).collect();
+    let versions: Vec<i32> = entries.iter().map(|entry| entry.version).collect();
+    let before: Vec<Option<serde_json::Value>> = entries.iter().map(|entry| entry.before.clone()).collect();
+    let after: Vec<Option<serde_json::Value>> = entries.iter().map(|entry| entry.after.clone()).collect();
+    sqlx::query!("INSERT INTO public.book_history(book_id, action, version, before, after, actor, request_id, record_timestamp)
+        SELECT u.book_id, u.action, u.version, u.before, u.after, $6, $7, $8
+        FROM UNNEST ($1::int4[], $2::text[], $3::int4[], $4::jsonb[], $5::jsonb[]) AS u(book_id, action, version, before, after)",
+        &book_ids[..],
+        &actions[..],
+        &versions[..],
+        &before[..] as _,
+        &after[..] as _,
+        audit.actor,
+        audit.request_id,
+        Utc::now().naive_utc())
+        .execute(connection)
+        .await?;
+    Ok(())
+}
+
+//The history is kept after the book is purged. The books inserted before the history existed have none.
+#[instrument(skip_all)]
+pub async fn db_read_history(book_id: i32, pool: &PgPool) -> Result<Vec<BookRevision>, ServiceError> {
+    let _timer = QueryTimer::start("db_read_history");
+    let revisions = sqlx::query_as!(BookRevision, r#"SELECT id AS revision, book_id, action, actor, request_id, version, before, after, record_timestamp AS recorded_at
+        FROM public.book_history
+        WHERE book_id = $1
+        ORDER BY id"#,
+        book_id)
+        .fetch_all(pool)
+        .await?;
+    if revisions.is_empty() {
+        let exists = sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM public.books WHERE id = $1) AS "exists!""#, book_id)
+            .fetch_one(pool)
+            .await?;
+        if !exists {
+            return Err(ServiceError::NotFound("Book not found.".into()));
+        }
+    }
+    Ok(revisions)
+}
@@ -1,55 +1,195 @@
-use actix_web::{error, http::StatusCode, HttpResponse, Result};
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::{error, http::header, http::StatusCode, HttpRequest, HttpResponse, Result};
 use serde::Serialize;
 use sqlx::error::Error as SQLxError;
 use std::fmt;
+use utoipa::ToSchema;
+use validator::ValidationErrors;
+use crate::telemetry::current_request_id;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
 
 #[derive(Debug, Serialize)]
 pub enum ServiceError {
     DBError(String),
     ActixError(String),
     NotFound(String),
+    BadRequest(String),
+    Validation(String),
+    InvalidFields(Vec<FieldError>),
+    Conflict(String),
+    PreconditionFailed(String),
+    Unavailable(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    Unauthorized(String),
+    Forbidden(String),
+    TooManyRequests(String),
+    NotAcceptable(String),
 }
 
 impl ServiceError {
-    fn error_response(&self) -> String {
+    //Stable machine-readable code sent in the "code" member of the problem details.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::DBError(_msg) => "database_error",
+            ServiceError::ActixError(_msg) => "internal_error",
+            ServiceError::NotFound(_msg) => "not_found",
+            ServiceError::BadRequest(_msg) => "bad_request",
+            ServiceError::Validation(_) | ServiceError::InvalidFields(_) => "validation_failed",
+            ServiceError::Conflict(_msg) => "conflict",
+            ServiceError::PreconditionFailed(_msg) => "precondition_failed",
+            ServiceError::Unavailable(_msg) => "service_unavailable",
+            ServiceError::PayloadTooLarge(_msg) => "payload_too_large",
+            ServiceError::UnsupportedMediaType(_msg) => "unsupported_media_type",
+            ServiceError::Unauthorized(_msg) => "unauthorized",
+            ServiceError::Forbidden(_msg) => "forbidden",
+            ServiceError::TooManyRequests(_msg) => "too_many_requests",
+            ServiceError::NotAcceptable(_msg) => "not_acceptable",
+        }
+    }
+
+    //Message sent to the client, the internal errors are only logged.
+    fn detail(&self) -> String {
         match self {
             ServiceError::DBError(msg) => {
-                println!("Database error occurred: {:?}", msg);
+                tracing::error!(error = %msg, "database error");
                 "Database error".into()
             }
             ServiceError::ActixError(msg) => {
-                println!("Server error occurred: {:?}", msg);
+                tracing::error!(error = %msg, "server error");
                 "Internal server error".into()
             }
-            ServiceError::NotFound(msg) => {
-                println!("Not found error occurred: {:?}", msg);
-                msg.into()
+            ServiceError::Unavailable(msg) => {
+                tracing::warn!(error = %msg, "service unavailable");
+                "The service is temporarily unavailable, try again later.".into()
             }
+            ServiceError::InvalidFields(_) => "The request has invalid fields.".into(),
+            ServiceError::NotFound(msg)
+            | ServiceError::BadRequest(msg)
+            | ServiceError::Validation(msg)
+            | ServiceError::Conflict(msg)
+            | ServiceError::PreconditionFailed(msg)
+            | ServiceError::PayloadTooLarge(msg)
+            | ServiceError::UnsupportedMediaType(msg)
+            | ServiceError::Unauthorized(msg)
+            | ServiceError::Forbidden(msg)
+            | ServiceError::TooManyRequests(msg)
+            | ServiceError::NotAcceptable(msg) => msg.into(),
         }
     }
 }
 
 impl fmt::Display for ServiceError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self)
+        match self {
+            ServiceError::DBError(msg) => write!(f, "Database error: {}", msg),
+            ServiceError::ActixError(msg) => write!(f, "Server error: {}", msg),
+            ServiceError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            ServiceError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+            ServiceError::Validation(msg) => write!(f, "Validation failed: {}", msg),
+            ServiceError::InvalidFields(errors) => {
+                let fields: Vec<String> = errors.iter().map(|error| format!("{} {}", error.field, error.message)).collect();
+                write!(f, "Validation failed: {}", fields.join(" "))
+            }
+            ServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            ServiceError::PreconditionFailed(msg) => write!(f, "Precondition failed: {}", msg),
+            ServiceError::Unavailable(msg) => write!(f, "Service unavailable: {}", msg),
+            ServiceError::PayloadTooLarge(msg) => write!(f, "Payload too large: {}", msg),
+            ServiceError::UnsupportedMediaType(msg) => write!(f, "Unsupported media type: {}", msg),
+            ServiceError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            ServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            ServiceError::TooManyRequests(msg) => write!(f, "Too many requests: {}", msg),
+            ServiceError::NotAcceptable(msg) => write!(f, "Not acceptable: {}", msg),
+        }
     }
 }
 
+//Needed to send the errors of a streamed body.
+impl std::error::Error for ServiceError {}
+
 impl From<actix_web::error::Error> for ServiceError {
     fn from(err: actix_web::error::Error) -> Self {
         ServiceError::ActixError(err.to_string())
     }
 }
 
+//https://www.postgresql.org/docs/current/errcodes-appendix.html
 impl From<SQLxError> for ServiceError {
     fn from(err: SQLxError) -> Self {
-        ServiceError::DBError(err.to_string())
+        match &err {
+            SQLxError::RowNotFound => ServiceError::NotFound("Record not found.".into()),
+            SQLxError::Database(db_err) => match db_err.code().as_deref() {
+                Some("23505") => ServiceError::Conflict("The record already exists.".into()),
+                Some("23503") => ServiceError::Conflict("The record is referenced by or references another record.".into()),
+                Some("23502") | Some("23514") => ServiceError::Validation("The record violates a database constraint.".into()),
+                Some("22001") => ServiceError::Validation("A value is too long.".into()),
+                Some("22003") | Some("22P02") => ServiceError::Validation("A value is out of range or has an invalid format.".into()),
+                Some("40001") | Some("40P01") => ServiceError::Conflict("The record was changed by a concurrent transaction, try again.".into()),
+                Some(code) if code.starts_with("08") || code.starts_with("53") || code.starts_with("57P") => {
+                    ServiceError::Unavailable(err.to_string())
+                }
+                _ => ServiceError::DBError(err.to_string()),
+            },
+            SQLxError::Io(_)
+            | SQLxError::Tls(_)
+            | SQLxError::PoolTimedOut
+            | SQLxError::PoolClosed
+            | SQLxError::WorkerCrashed => ServiceError::Unavailable(err.to_string()),
+            _ => ServiceError::DBError(err.to_string()),
+        }
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    error_message: String,
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    //The prefix locates the validated value inside the payload, e.g. "[2]." for the third book of a bulk insert.
+    pub fn from_validation_errors(prefix: &str, errors: &ValidationErrors) -> Vec<FieldError> {
+        let mut field_errors: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: format!("{}{}", prefix, field),
+                    code: error.code.to_string(),
+                    message: error
+                        .message
+                        .as_ref()
+                        .map_or_else(|| format!("The value is invalid ({}).", error.code), |message| message.to_string()),
+                })
+            })
+            .collect();
+        field_errors.sort_by(|a, b| a.field.cmp(&b.field));
+        field_errors
+    }
+}
+
+impl From<ValidationErrors> for ServiceError {
+    fn from(errors: ValidationErrors) -> Self {
+        ServiceError::InvalidFields(FieldError::from_validation_errors("", &errors))
+    }
+}
+
+//RFC 7807 problem details, the invalid fields are sent in the "errors" extension member and the id of the
+//request in "request_id" so a report can be found in the logs.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<Vec<FieldError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl error::ResponseError for ServiceError {
@@ -59,11 +199,64 @@ impl error::ResponseError for ServiceError {
                 StatusCode::INTERNAL_SERVER_ERROR
             }
             ServiceError::NotFound(_msg) => StatusCode::NOT_FOUND,
+            ServiceError::BadRequest(_msg) => StatusCode::BAD_REQUEST,
+            ServiceError::Validation(_) | ServiceError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ServiceError::Conflict(_msg) => StatusCode::CONFLICT,
+            ServiceError::PreconditionFailed(_msg) => StatusCode::PRECONDITION_FAILED,
+            ServiceError::Unavailable(_msg) => StatusCode::SERVICE_UNAVAILABLE,
+            ServiceError::PayloadTooLarge(_msg) => StatusCode::PAYLOAD_TOO_LARGE,
+            ServiceError::UnsupportedMediaType(_msg) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ServiceError::Unauthorized(_msg) => StatusCode::UNAUTHORIZED,
+            ServiceError::Forbidden(_msg) => StatusCode::FORBIDDEN,
+            ServiceError::TooManyRequests(_msg) => StatusCode::TOO_MANY_REQUESTS,
+            ServiceError::NotAcceptable(_msg) => StatusCode::NOT_ACCEPTABLE,
         }
     }
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).json(ErrorResponse {
-            error_message: self.error_response(),
-        })
+        let status = self.status_code();
+        let mut response = HttpResponse::build(status);
+        if let ServiceError::Unauthorized(_msg) = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response
+            .content_type(PROBLEM_JSON)
+            .json(ProblemDetails {
+                problem_type: format!("urn:library-service:problem:{}", self.code()),
+                title: status.canonical_reason().unwrap_or("Error").into(),
+                status: status.as_u16(),
+                detail: self.detail(),
+                code: self.code(),
+                errors: match self {
+                    ServiceError::InvalidFields(errors) => Some(errors.clone()),
+                    _ => None,
+                },
+                request_id: current_request_id(),
+            })
     }
-}
\ No newline at end of file
+}
+
+//Error handlers registered in the App so the extractor errors are also sent as problem details.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> error::Error {
+    match err {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            ServiceError::PayloadTooLarge(err.to_string())
+        }
+        JsonPayloadError::ContentType => {
+            ServiceError::UnsupportedMediaType("The content type must be application/json.".into())
+        }
+        JsonPayloadError::Deserialize(json_err) if json_err.is_data() => {
+            ServiceError::Validation(json_err.to_string())
+        }
+        JsonPayloadError::Deserialize(json_err) => ServiceError::BadRequest(format!("Malformed JSON body: {}", json_err)),
+        _ => ServiceError::BadRequest(err.to_string()),
+    }
+    .into()
+}
+
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> error::Error {
+    ServiceError::BadRequest(err.to_string()).into()
+}
+
+pub fn path_error_handler(err: PathError, _req: &HttpRequest) -> error::Error {
+    ServiceError::BadRequest(err.to_string()).into()
+}
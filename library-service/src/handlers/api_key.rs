@@ -0,0 +1,303 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use validator::Validate;
+use crate::dal::api_key::*;
+use crate::errors::{FieldError, ServiceError};
+use crate::model::api_key::{generate_api_key, hash_api_key, IssuedApiKey, NewApiKey};
+use crate::state::AppState;
+use tracing::instrument;
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/api-keys",
+    tag = "api-keys",
+    request_body = NewApiKey,
+    responses(
+        (status = 201, description = "The key, only sent in this response.", body = IssuedApiKey),
+        (status = 422, description = "The key is invalid.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "The credentials are missing or invalid.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The role of the caller is not allowed.", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = [])),
+)]
+#[instrument(skip_all)]
+pub async fn post_api_key(
+    new_api_key: web::Json<NewApiKey>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ServiceError> {
+    new_api_key.validate()?;
+    if new_api_key.expires_at.is_some_and(|expires_at| expires_at <= Utc::now().naive_utc()) {
+        return Err(ServiceError::InvalidFields(vec![FieldError {
+            field: "expires_at".into(),
+            code: "expired".into(),
+            message: "The expiry must be in the future.".into(),
+        }]));
+    }
+    let (key, prefix) = generate_api_key().map_err(|err| ServiceError::ActixError(err.to_string()))?;
+    db_create_api_key(&app_state.db, new_api_key.into_inner(), &hash_api_key(&key), &prefix)
+        .await
+        .map(|api_key| HttpResponse::Created().json(IssuedApiKey { api_key, key }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/api-keys",
+    tag = "api-keys",
+    responses(
+        (status = 200, description = "The keys, without the keys themselves.", body = Vec<ApiKey>),
+        (status = 401, description = "The credentials are missing or invalid.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The role of the caller is not allowed.", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = [])),
+)]
+#[instrument(skip_all)]
+pub async fn get_api_keys(
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ServiceError> {
+    db_read_api_keys(&app_state.db).await
+        .map(|api_keys| HttpResponse::Ok().json(api_keys))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/api-keys/{id}/rotate",
+    tag = "api-keys",
+    params(
+        ("id" = i32, Path, description = "Id of the API key."),
+    ),
+    responses(
+        (status = 200, description = "The new key, only sent in this response.", body = IssuedApiKey),
+        (status = 404, description = "The key does not exist.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "The credentials are missing or invalid.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The role of the caller is not allowed.", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = [])),
+)]
+#[instrument(skip_all)]
+pub async fn post_rotate_api_key(
+    param: web::Path<i32>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ServiceError> {
+    let tuple = param.into_inner();
+    let id: i32 = tuple;
+    let (key, prefix) = generate_api_key().map_err(|err| ServiceError::ActixError(err.to_string()))?;
+    db_rotate_api_key(id, &hash_api_key(&key), &prefix, &app_state.db)
+        .await
+        .map(|api_key| HttpResponse::Ok().json(IssuedApiKey { api_key, key }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/api-keys/{id}",
+    tag = "api-keys",
+    params(
+        ("id" = i32, Path, description = "Id of the API key."),
+    ),
+    responses(
+        (status = 200, description = "The revoked key.", body = ApiKey),
+        (status = 404, description = "The key does not exist.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "The credentials are missing or invalid.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The role of the caller is not allowed.", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = [])),
+)]
+#[instrument(skip_all)]
+pub async fn delete_api_key(
+    param: web::Path<i32>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ServiceError> {
+    let tuple = param.into_inner();
+    let id: i32 = tuple;
+    db_revoke_api_key(id, &app_state.db).await
+        .map(|api_key| HttpResponse::Ok().json(api_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use crate::auth::{AuthConfig, Claims};
+    use crate::routes::{admin_routes, book_routes, error_handlers};
+    use crate::test_utils::test_state;
+
+    const TEST_JWT_SECRET: &str = "unit-test-secret";
+
+    fn bearer_token(subject: &str, roles: &[&str]) -> String {
+        let claims = Claims {
+            sub: subject.into(),
+            exp: jsonwebtoken::get_current_timestamp() + 600,
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+        };
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(TEST_JWT_SECRET.as_bytes()),
+        )
+        .unwrap();
+        format!("Bearer {}", token)
+    }
+
+    #[actix_rt::test]
+    async fn test_api_keys() {
+        let shared_data = test_state().await;
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .app_data(web::Data::new(AuthConfig::new(Some(TEST_JWT_SECRET), None)))
+            .configure(error_handlers)
+            .configure(book_routes)
+            .configure(admin_routes)).await;
+        let admin = bearer_token("admin-1", &["admin"]);
+        let key_name = format!("batch-job-{}", Utc::now().timestamp_nanos());
+
+        let request = test::TestRequest::post()
+            .uri("/admin/api-keys")
+            .insert_header((header::AUTHORIZATION, bearer_token("librarian-1", &["librarian"])))
+            .set_json(serde_json::json!({"name": key_name, "scopes": ["librarian"]}))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::FORBIDDEN);
+
+        //The admin role is bound to the routes, whatever the encoding of their path.
+        for uri in ["/%61dmin/api-keys", "/admin/api-key%73", "/%61%64%6D%69%6E/api-keys"] {
+            let request = test::TestRequest::post()
+                .uri(uri)
+                .insert_header((header::AUTHORIZATION, bearer_token("librarian-1", &["librarian"])))
+                .set_json(serde_json::json!({"name": key_name, "scopes": ["admin"]}))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::FORBIDDEN, "{}", uri);
+        }
+
+        let request = test::TestRequest::post()
+            .uri("/admin/api-keys")
+            .insert_header((header::AUTHORIZATION, admin.clone()))
+            .set_json(serde_json::json!({"name": key_name, "scopes": ["librarian"]}))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::CREATED);
+        let issued: serde_json::Value = test::read_body_json(http_response).await;
+        let key = issued["key"].as_str().unwrap().to_string();
+        let id = issued["id"].as_i64().unwrap();
+        assert!(key.starts_with(issued["prefix"].as_str().unwrap()));
+
+        let request = test::TestRequest::post()
+            .uri("/admin/api-keys")
+            .insert_header((header::AUTHORIZATION, admin.clone()))
+            .set_json(serde_json::json!({"name": key_name, "scopes": ["reader"]}))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::CONFLICT);
+
+        let request = test::TestRequest::post()
+            .uri("/library/")
+            .insert_header(("X-API-Key", key.as_str()))
+            .insert_header(("X-Request-Id", "api-key-test"))
+            .set_json(serde_json::json!({"title": "Unit Test title key", "author": "Unit Test author key"}))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::CREATED);
+        let book: serde_json::Value = test::read_body_json(http_response).await;
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/library/{}", book["id"]))
+            .insert_header(("X-API-Key", key.as_str()))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/library/{}/history", book["id"]))
+            .insert_header(("X-API-Key", key.as_str()))
+            .to_request();
+        let history: Vec<serde_json::Value> = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(history[0]["actor"], format!("api-key:{}", key_name));
+
+        let request = test::TestRequest::get()
+            .uri("/admin/api-keys")
+            .insert_header((header::AUTHORIZATION, admin.clone()))
+            .to_request();
+        let api_keys: Vec<serde_json::Value> = test::call_and_read_body_json(&app, request).await;
+        let api_key = api_keys.iter().find(|api_key| api_key["id"] == id).unwrap();
+        assert_eq!(api_key["request_count"], 3);
+        assert!(api_key["last_used_at"].is_string());
+        assert!(api_key.get("key").is_none());
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/admin/api-keys/{}/rotate", id))
+            .insert_header((header::AUTHORIZATION, admin.clone()))
+            .to_request();
+        let rotated: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let rotated_key = rotated["key"].as_str().unwrap().to_string();
+        assert_ne!(rotated_key, key);
+        let request = test::TestRequest::get()
+            .uri("/library")
+            .insert_header(("X-API-Key", key.as_str()))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::UNAUTHORIZED);
+        let request = test::TestRequest::get()
+            .uri("/library")
+            .insert_header(("X-API-Key", rotated_key.as_str()))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/admin/api-keys/{}", id))
+            .insert_header((header::AUTHORIZATION, admin.clone()))
+            .to_request();
+        let revoked: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert!(revoked["revoked_at"].is_string());
+        let request = test::TestRequest::get()
+            .uri("/library")
+            .insert_header(("X-API-Key", rotated_key.as_str()))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::UNAUTHORIZED);
+        let request = test::TestRequest::delete()
+            .uri(&format!("/admin/api-keys/{}", id))
+            .insert_header((header::AUTHORIZATION, admin))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_expired_api_key() {
+        let shared_data = test_state().await;
+        let app = test::init_service(App::new()
+            .app_data(shared_data.clone())
+            .app_data(web::Data::new(AuthConfig::new(Some(TEST_JWT_SECRET), None)))
+            .configure(error_handlers)
+            .configure(book_routes)
+            .configure(admin_routes)).await;
+        let admin = bearer_token("admin-1", &["admin"]);
+
+        let request = test::TestRequest::post()
+            .uri("/admin/api-keys")
+            .insert_header((header::AUTHORIZATION, admin.clone()))
+            .set_json(serde_json::json!({"name": "expired-job", "scopes": ["reader"], "expires_at": "2020-01-01T00:00:00"}))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let request = test::TestRequest::post()
+            .uri("/admin/api-keys")
+            .insert_header((header::AUTHORIZATION, admin))
+            .set_json(serde_json::json!({"name": format!("expiring-job-{}", Utc::now().timestamp_nanos()), "scopes": ["reader"], "expires_at": "2999-01-01T00:00:00"}))
+            .to_request();
+        let issued: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        sqlx::query("UPDATE public.api_keys SET expires_at = now() - interval '1 day' WHERE id = $1")
+            .bind(issued["id"].as_i64().unwrap() as i32)
+            .execute(&shared_data.db)
+            .await
+            .unwrap();
+        let request = test::TestRequest::get()
+            .uri("/library")
+            .insert_header(("X-API-Key", issued["key"].as_str().unwrap()))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
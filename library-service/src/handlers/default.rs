@@ -1,9 +1,41 @@
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
+use crate::errors::ServiceError;
+use crate::routes::API_PREFIX;
 use crate::state::AppState;
+use crate::versioning::{requested_version, ApiVersion};
+use tracing::instrument;
 
+#[utoipa::path(
+    get,
+    path = "/probe",
+    tag = "operations",
+    responses(
+        (status = 200, description = "The service answers.", body = String),
+    ),
+)]
+#[instrument(skip_all)]
 pub async fn get_probe(app_state: web::Data<AppState>) -> HttpResponse {
     let probe_response = &app_state.probe;
-    HttpResponse::Ok().json(&probe_response)
+    HttpResponse::Ok().json(probe_response)
+}
+
+//Answers the routes of the features disabled in the settings.
+#[instrument(skip_all)]
+pub async fn feature_disabled() -> Result<HttpResponse, ServiceError> {
+    Err(ServiceError::NotFound("This feature is disabled.".into()))
+}
+
+//Answers the API requests which match no route, 406 when Accept asks for a version which is not served at the path.
+#[instrument(skip_all)]
+pub async fn api_not_found(req: HttpRequest) -> Result<HttpResponse, ServiceError> {
+    let path_version = req.path().strip_prefix(API_PREFIX).and_then(|path| path.split('/').nth(1)).and_then(ApiVersion::parse);
+    match requested_version(req.headers()) {
+        Some(Err(version)) => Err(ServiceError::NotAcceptable(format!("The version {} of the API does not exist.", version))),
+        Some(Ok(version)) if path_version.is_some_and(|path_version| path_version != version) => Err(
+            ServiceError::NotAcceptable(format!("The path is not a route of the version {} of the API.", version.number())),
+        ),
+        _ => Err(ServiceError::NotFound("Resource not found.".into())),
+    }
 }
 
 #[cfg(test)]
@@ -13,11 +45,29 @@ mod tests {
     use actix_web::web;
     use dotenv::dotenv;
     use sqlx::postgres::PgPoolOptions;
+    use actix_web::http::header;
+    use actix_web::{test, App};
     use crate::handlers::default::get_probe;
+    use crate::model::book::DEFAULT_TRASH_RETENTION_DAYS;
+    use crate::errors::PROBLEM_JSON;
+    use crate::routes::{api_routes, book_routes, error_handlers, general_routes};
+    use crate::settings::{ApiSettings, FeatureSettings};
     use crate::state::AppState;
+    use crate::telemetry::{RequestTracing, REQUEST_ID_HEADER};
+    use crate::test_utils::test_state;
+    use chrono::{TimeZone, Utc};
 
     #[actix_rt::test]
-    async fn test_add_book() {
+    async fn test_probe() {
+        let shared_data = test_state().await;
+        let http_response = get_probe(shared_data).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(http_response.into_body()).await.unwrap();
+        assert_eq!(body, "\"Probe test ok....\"");
+    }
+
+    #[actix_rt::test]
+    async fn test_request_id() {
         dotenv().ok();
         let database_url = env::var("DATABASE_URL")
             .expect("DATABASE_URL is not set in .env file");
@@ -29,9 +79,133 @@ mod tests {
         let shared_data = web::Data::new(AppState {
             probe: "Probe test ok....".to_string(),
             db: db_pool,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
         });
+        let app = test::init_service(App::new()
+            .wrap(RequestTracing { access_log: true })
+            .app_data(shared_data)
+            .configure(error_handlers)
+            .configure(general_routes)
+            .configure(book_routes)).await;
 
-        let http_response = get_probe(shared_data).await;
+        let request = test::TestRequest::get()
+            .uri("/library/999999")
+            .insert_header((REQUEST_ID_HEADER, "support-ticket-42"))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(http_response.headers().get(REQUEST_ID_HEADER).unwrap(), "support-ticket-42");
+        let problem: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(problem["request_id"], "support-ticket-42");
+
+        //The extractor errors are also sent with the id.
+        let request = test::TestRequest::get()
+            .uri("/library/not-a-number")
+            .insert_header((REQUEST_ID_HEADER, "support-ticket-43"))
+            .to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::BAD_REQUEST);
+        let problem: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(problem["request_id"], "support-ticket-43");
+
+        //A missing or invalid id is replaced by a generated UUID.
+        for request_id in [None, Some("x".repeat(141)), Some("with space".to_string())] {
+            let mut request = test::TestRequest::get().uri("/probe");
+            if let Some(request_id) = &request_id {
+                request = request.insert_header((REQUEST_ID_HEADER, request_id.as_str()));
+            }
+            let http_response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            let generated = http_response.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+            assert!(uuid::Uuid::parse_str(generated).is_ok(), "{} is not a UUID", generated);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_api_versions() {
+        let shared_data = test_state().await;
+        let settings = ApiSettings {
+            legacy_routes: true,
+            deprecated_at: Some(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()),
+            sunset_at: Some(Utc.with_ymd_and_hms(2030, 7, 1, 12, 30, 0).unwrap()),
+        };
+        let app = test::init_service(App::new()
+            .app_data(shared_data.clone())
+            .configure(error_handlers)
+            .configure(|cfg| api_routes(cfg, &FeatureSettings::default(), &settings))).await;
+
+        //The version is given by the path, else by the Accept header.
+        for accept in [None, Some("application/json; version=1"), Some("application/json;version=\"v1\"")] {
+            let mut request = test::TestRequest::get().uri("/api/v1/library/1");
+            if let Some(accept) = accept {
+                request = request.insert_header((header::ACCEPT, accept));
+            }
+            let http_response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            assert!(http_response.headers().get("deprecation").is_none());
+            assert!(http_response.headers().get("sunset").is_none());
+        }
+
+        //The routes without version are the deprecated v1 routes.
+        for accept in ["*/*", "application/json; q=0.9; version=1"] {
+            let request = test::TestRequest::get()
+                .uri("/api/library/1")
+                .insert_header((header::ACCEPT, accept))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            assert_eq!(http_response.headers().get("deprecation").unwrap(), "@1893456000");
+            assert_eq!(http_response.headers().get("sunset").unwrap(), "Mon, 01 Jul 2030 12:30:00 GMT");
+            assert_eq!(
+                http_response.headers().get(header::LINK).unwrap(),
+                "</api/v1/library/1>; rel=\"successor-version\""
+            );
+            assert_eq!(http_response.headers().get(header::VARY).unwrap(), "accept");
+        }
+        let request = test::TestRequest::get().uri("/api/library").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        assert!(http_response.headers().get("deprecation").is_some());
+
+        //A version which does not exist, or which is not the version of the path, is not acceptable.
+        for (uri, accept) in [
+            ("/api/library/1", "application/json; version=7"),
+            ("/api/v1/library/1", "application/json; version=v2"),
+            ("/api/v1/library/1", "application/json; version=latest"),
+        ] {
+            let request = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ACCEPT, accept))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::NOT_ACCEPTABLE, "{} {}", uri, accept);
+            assert_eq!(http_response.headers().get(header::CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+            let problem: serde_json::Value = test::read_body_json(http_response).await;
+            assert_eq!(problem["code"], "not_acceptable");
+        }
+        for uri in ["/api/v9/library/1", "/api/unknown"] {
+            let request = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ACCEPT, "application/json; version=1"))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+
+        //The aliases are removed after the sunset.
+        let settings = ApiSettings {
+            legacy_routes: false,
+            ..settings
+        };
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .configure(error_handlers)
+            .configure(|cfg| api_routes(cfg, &FeatureSettings::default(), &settings))).await;
+        let request = test::TestRequest::get().uri("/api/library/1").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+        let request = test::TestRequest::get().uri("/api/v1/library/1").to_request();
+        let http_response = test::call_service(&app, request).await;
         assert_eq!(http_response.status(), StatusCode::OK);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,224 @@
+use actix_web::{web, HttpResponse};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use crate::dal::health::db_ping;
+use crate::model::health::{ComponentHealth, HealthReport, HealthStatus};
+use crate::settings::HealthSettings;
+use crate::shutdown::ShutdownState;
+use crate::state::AppState;
+use tracing::instrument;
+
+//The process answers, the dependencies are not checked so a database outage does not restart the pod.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "operations",
+    responses(
+        (status = 200, description = "The process answers.", body = HealthReport),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn get_live() -> HttpResponse {
+    HttpResponse::Ok().json(HealthReport::new(BTreeMap::new()))
+}
+
+//503 Service Unavailable when a dependency is down or when the service shuts down, the pod then stops receiving
+//traffic.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Every dependency is up.", body = HealthReport),
+        (status = 503, description = "A dependency is down or the service shuts down.", body = HealthReport),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn get_ready(
+    app_state: web::Data<AppState>,
+    settings: web::Data<HealthSettings>,
+    shutdown_state: Option<web::Data<ShutdownState>>,
+) -> HttpResponse {
+    let mut components = BTreeMap::new();
+    if shutdown_state.is_some_and(|state| state.is_draining()) {
+        components.insert("server", ComponentHealth {
+            status: HealthStatus::Down,
+            latency_ms: 0,
+            error: Some("The service is shutting down.".into()),
+        });
+        return HttpResponse::ServiceUnavailable().json(HealthReport::new(components));
+    }
+    let timeout = Duration::from_millis(settings.timeout_ms);
+    components.insert("database", check(timeout, async {
+        db_ping(&app_state.db).await.map_err(|err| {
+            tracing::warn!(error = %err, "health check of the database failed");
+            "The database is unreachable.".to_string()
+        })
+    }).await);
+    if let Some(ipfs_api_url) = &settings.ipfs_api_url {
+        components.insert("ipfs", check(timeout, ping_ipfs(ipfs_api_url, timeout)).await);
+    }
+    let report = HealthReport::new(components);
+    match report.status {
+        HealthStatus::Up => HttpResponse::Ok().json(report),
+        HealthStatus::Down => HttpResponse::ServiceUnavailable().json(report),
+    }
+}
+
+async fn check(timeout: Duration, check: impl Future<Output = Result<(), String>>) -> ComponentHealth {
+    let start = Instant::now();
+    let result = match actix_rt::time::timeout(timeout, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("No answer within {} ms.", timeout.as_millis())),
+    };
+    ComponentHealth {
+        status: if result.is_ok() { HealthStatus::Up } else { HealthStatus::Down },
+        latency_ms: start.elapsed().as_millis() as u64,
+        error: result.err(),
+    }
+}
+
+//The Kubo RPC API only accepts POST requests.
+async fn ping_ipfs(ipfs_api_url: &str, timeout: Duration) -> Result<(), String> {
+    let url = format!("{}/api/v0/version", ipfs_api_url.trim_end_matches('/'));
+    match awc::Client::builder().timeout(timeout).finish().post(url).send().await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("The IPFS API answered {}.", response.status())),
+        Err(err) => {
+            tracing::warn!(error = %err, "health check of the IPFS API failed");
+            Err("The IPFS API is unreachable.".into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::dev::Service;
+    use actix_web::{test, App};
+    use crate::routes::health_routes;
+    use crate::shutdown::{DrainSummary, InFlightRequests};
+    use crate::test_utils::{app_state, test_state};
+    use sqlx::postgres::PgPoolOptions;
+
+    #[actix_rt::test]
+    async fn test_health() {
+        let shared_data = test_state().await;
+        let app = test::init_service(App::new()
+            .app_data(shared_data.clone())
+            .configure(|cfg| health_routes(cfg, &HealthSettings::default()))).await;
+
+        let request = test::TestRequest::get().uri("/health/live").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        let report: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(report, serde_json::json!({"status": "up"}));
+
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        let report: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(report["status"], "up");
+        assert_eq!(report["components"]["database"]["status"], "up");
+        assert!(report["components"].get("ipfs").is_none());
+
+        //Nothing listens on the port 9 of the loopback.
+        let settings = HealthSettings {
+            timeout_ms: 1000,
+            ipfs_api_url: Some("http://127.0.0.1:9".into()),
+        };
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .configure(|cfg| health_routes(cfg, &settings))).await;
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(report["status"], "down");
+        assert_eq!(report["components"]["database"]["status"], "up");
+        assert_eq!(report["components"]["ipfs"]["status"], "down");
+        assert_eq!(report["components"]["ipfs"]["error"], "The IPFS API is unreachable.");
+    }
+
+    #[actix_rt::test]
+    async fn test_health_database_outage() {
+        //The listener never answers and the pool waits longer than the check, the check gives up first.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let database_url = format!("postgres://library:library@{}/library", listener.local_addr().unwrap());
+        let db_pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_secs(10))
+            .connect_lazy(&database_url)
+            .unwrap();
+        let shared_data = app_state(db_pool);
+        let settings = HealthSettings {
+            timeout_ms: 500,
+            ipfs_api_url: None,
+        };
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .configure(|cfg| health_routes(cfg, &settings))).await;
+
+        let request = test::TestRequest::get().uri("/health/live").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(report["components"]["database"]["status"], "down");
+        assert_eq!(report["components"]["database"]["error"], "No answer within 500 ms.");
+        assert!(report["components"]["database"]["latency_ms"].as_u64().unwrap() < 5000);
+    }
+
+    #[actix_rt::test]
+    async fn test_health_shutdown() {
+        let shared_data = test_state().await;
+        let shutdown_state = web::Data::new(ShutdownState::default());
+        let app = test::init_service(App::new()
+            .wrap(InFlightRequests)
+            .app_data(shared_data)
+            .app_data(shutdown_state.clone())
+            .configure(|cfg| health_routes(cfg, &HealthSettings::default()))).await;
+
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        //The request stays in flight until its body is sent.
+        assert_eq!(shutdown_state.in_flight(), 1);
+        test::read_body(http_response).await;
+        assert_eq!(shutdown_state.in_flight(), 0);
+
+        assert_eq!(shutdown_state.start_draining(), 0);
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = test::read_body_json(http_response).await;
+        assert_eq!(report["status"], "down");
+        assert_eq!(report["components"]["server"]["error"], "The service is shutting down.");
+        assert!(report["components"].get("database").is_none());
+
+        //The process is still alive while it drains the requests.
+        let request = test::TestRequest::get().uri("/health/live").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        test::read_body(http_response).await;
+        assert_eq!(shutdown_state.summary(), DrainSummary { drained: 2, aborted: 0 });
+
+        //A request dropped before its response is aborted.
+        let request = test::TestRequest::get().uri("/health/live").to_request();
+        let future = app.call(request);
+        assert_eq!(shutdown_state.in_flight(), 1);
+        drop(future);
+        assert_eq!(shutdown_state.summary(), DrainSummary { drained: 2, aborted: 1 });
+
+        //So is a response dropped before the end of its body.
+        let request = test::TestRequest::get().uri("/health/live").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(shutdown_state.in_flight(), 1);
+        drop(http_response);
+        assert_eq!(shutdown_state.summary(), DrainSummary { drained: 2, aborted: 2 });
+    }
+}
@@ -0,0 +1,71 @@
+use actix_web::{web, HttpResponse};
+use prometheus::TEXT_FORMAT;
+use crate::errors::ServiceError;
+use crate::metrics::METRICS;
+use crate::state::AppState;
+use tracing::instrument;
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses(
+        (status = 200, description = "The metrics in the Prometheus text format.", body = String, content_type = "text/plain; version=0.0.4"),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn get_metrics(
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ServiceError> {
+    METRICS
+        .render(&app_state.db)
+        .map(|metrics| HttpResponse::Ok().content_type(TEXT_FORMAT).body(metrics))
+        .map_err(ServiceError::ActixError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, App};
+    use crate::metrics::RequestMetrics;
+    use crate::routes::{book_routes, error_handlers, metrics_routes};
+    use crate::test_utils::test_state;
+
+    #[actix_rt::test]
+    async fn test_metrics() {
+        let shared_data = test_state().await;
+        let max_connections = format!("db_pool_max_connections {}", shared_data.db.options().get_max_connections());
+        let app = test::init_service(App::new()
+            .wrap(RequestMetrics)
+            .app_data(shared_data)
+            .configure(error_handlers)
+            .configure(metrics_routes)
+            .configure(book_routes)).await;
+
+        for uri in ["/library/1", "/library/999999", "/not/a/route/42"] {
+            let request = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&app, request).await;
+        }
+
+        let request = test::TestRequest::get().uri("/metrics").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        assert_eq!(http_response.headers().get(header::CONTENT_TYPE).unwrap(), TEXT_FORMAT);
+        let body = String::from_utf8(test::read_body(http_response).await.to_vec()).unwrap();
+        for metric in [
+            r#"http_requests_total{method="GET",route="/library/{id}",status="200"}"#,
+            r#"http_requests_total{method="GET",route="/library/{id}",status="404"}"#,
+            r#"http_requests_total{method="GET",route="unmatched",status="404"}"#,
+            r#"http_request_duration_seconds_bucket{method="GET",route="/library/{id}",le="0.005"}"#,
+            r#"db_query_duration_seconds_count{function="db_read_book_by_id"}"#,
+            r#"db_pool_connections{state="idle"}"#,
+            r#"db_pool_connections{state="in_use"}"#,
+            &max_connections,
+            "db_pool_waiters",
+        ] {
+            assert!(body.contains(metric), "{} is missing from:\n{}", metric, body);
+        }
+        assert!(!body.contains("/not/a/route"));
+    }
+}
@@ -1,2 +1,7 @@
+pub mod api_key;
 pub mod default;
-pub mod book;
\ No newline at end of file
+pub mod book;
+pub mod health;
+pub mod metrics;
+pub mod openapi;
+pub mod ui;
\ No newline at end of file
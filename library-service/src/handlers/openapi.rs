@@ -0,0 +1,135 @@
+use actix_web::HttpResponse;
+use utoipa::OpenApi;
+use crate::openapi::ApiDoc;
+use tracing::instrument;
+
+//Page of Swagger UI loaded from unpkg, it reads the document from /openapi.json.
+const API_DOCS_HTML: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>library-service API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: '/openapi.json', dom_id: '#swagger-ui' });
+    };
+  </script>
+</body>
+</html>"#;
+
+#[instrument(skip_all)]
+pub async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[instrument(skip_all)]
+pub async fn get_api_docs() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(API_DOCS_HTML)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use actix_web::http::{header, Method, StatusCode};
+    use actix_web::{test, web, App};
+    use crate::auth::AuthConfig;
+    use crate::routes::{api_routes, error_handlers, general_routes, health_routes, metrics_routes, openapi_routes};
+    use crate::settings::{ApiSettings, FeatureSettings, HealthSettings};
+    use crate::test_utils::test_state;
+    use serde_json::Value;
+    use utoipa::openapi::PathItemType;
+
+    const TEST_JWT_SECRET: &str = "unit-test-secret";
+    //Handlers of routes.rs which are not in the document.
+    const UNDOCUMENTED_HANDLERS: [&str; 4] = ["feature_disabled", "get_ui_file", "get_openapi", "get_api_docs"];
+
+    fn method(path_item_type: &PathItemType) -> Method {
+        match path_item_type {
+            PathItemType::Get => Method::GET,
+            PathItemType::Post => Method::POST,
+            PathItemType::Put => Method::PUT,
+            PathItemType::Patch => Method::PATCH,
+            PathItemType::Delete => Method::DELETE,
+            PathItemType::Head => Method::HEAD,
+            PathItemType::Options => Method::OPTIONS,
+            PathItemType::Trace => Method::TRACE,
+            PathItemType::Connect => Method::CONNECT,
+        }
+    }
+
+    //The handlers given to .to(...) in routes.rs.
+    fn routed_handlers() -> BTreeSet<String> {
+        include_str!("../routes.rs")
+            .split(".to(")
+            .skip(1)
+            .map(|rest| rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect::<String>())
+            .filter(|handler| !UNDOCUMENTED_HANDLERS.contains(&handler.as_str()))
+            .collect()
+    }
+
+    #[actix_rt::test]
+    async fn test_openapi() {
+        //Every route is documented, the operation ids are the names of the handlers.
+        let document = ApiDoc::openapi();
+        let operation_ids: BTreeSet<String> = document
+            .paths
+            .paths
+            .values()
+            .flat_map(|path_item| path_item.operations.values())
+            .filter_map(|operation| operation.operation_id.clone())
+            .collect();
+        assert_eq!(routed_handlers(), operation_ids, "a route of routes.rs is not in the OpenAPI document");
+
+        let shared_data = test_state().await;
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .app_data(web::Data::new(AuthConfig::new(Some(TEST_JWT_SECRET), None)))
+            .configure(error_handlers)
+            .configure(general_routes)
+            .configure(|cfg| health_routes(cfg, &HealthSettings::default()))
+            .configure(metrics_routes)
+            .configure(openapi_routes)
+            .configure(|cfg| api_routes(cfg, &FeatureSettings::default(), &ApiSettings::default()))).await;
+
+        //Every documented path is a route, the API routes answer 401 without credentials.
+        for (path, path_item) in &document.paths.paths {
+            let uri = path
+                .split('/')
+                .map(|segment| if segment.starts_with('{') { "1" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            for path_item_type in path_item.operations.keys() {
+                let request = test::TestRequest::default()
+                    .method(method(path_item_type))
+                    .uri(&uri)
+                    .to_request();
+                let http_response = test::call_service(&app, request).await;
+                assert_ne!(http_response.status(), StatusCode::NOT_FOUND, "{}", path);
+                assert_eq!(http_response.request().match_pattern().as_deref(), Some(path.as_str()));
+            }
+        }
+
+        let request = test::TestRequest::get().uri("/openapi.json").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        let openapi: Value = test::read_body_json(http_response).await;
+        assert!(openapi["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(openapi["paths"]["/api/v1/library/{id}"]["get"].is_object());
+        assert!(openapi["components"]["schemas"]["Book"].is_object());
+        assert_eq!(openapi["components"]["securitySchemes"]["bearer"]["scheme"], "bearer");
+        assert_eq!(openapi["components"]["securitySchemes"]["api_key"]["name"], "X-API-Key");
+
+        let request = test::TestRequest::get().uri("/docs").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        assert_eq!(http_response.headers().get(header::CONTENT_TYPE).unwrap(), "text/html; charset=utf-8");
+        let body = test::read_body(http_response).await;
+        assert!(std::str::from_utf8(&body).unwrap().contains("/openapi.json"));
+    }
+}
@@ -0,0 +1,221 @@
+use actix_files::NamedFile;
+use actix_web::http::header::{self, ContentEncoding, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{web, HttpRequest, HttpResponse};
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use crate::errors::ServiceError;
+use crate::routes::API_PREFIX;
+use crate::settings::UiSettings;
+use tracing::instrument;
+
+//The hashed files of the build never change, a new build gives them new names.
+const HASHED_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+//The other files (index.html, manifest.json...) are revalidated so a new build is loaded at once.
+const DEFAULT_CACHE_CONTROL: &str = "no-cache";
+
+//Answers the requests which match no other route with a file of the UI, else with its index.html so the routes
+//of the UI can be reloaded. The unknown API routes and the missing files are not found.
+#[instrument(skip_all)]
+pub async fn get_ui_file(req: HttpRequest, settings: web::Data<UiSettings>) -> Result<HttpResponse, ServiceError> {
+    let path = req.path();
+    let is_api = path == API_PREFIX || path.starts_with(&format!("{}/", API_PREFIX));
+    if is_api || !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return Err(ServiceError::NotFound("Resource not found.".into()));
+    }
+    let file = match ui_file(&settings.dir, path) {
+        Some(file) if file.is_file() => file,
+        _ if has_extension(path) => return Err(ServiceError::NotFound("File not found.".into())),
+        _ => settings.index_file(),
+    };
+    send_file(&req, &file).await
+}
+
+//None when a segment could leave the directory or names a hidden file.
+fn ui_file(dir: &Path, path: &str) -> Option<PathBuf> {
+    let mut file = dir.to_path_buf();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        if segment.starts_with('.') || segment.contains('\\') {
+            return None;
+        }
+        file.push(segment);
+    }
+    Some(file)
+}
+
+//The routes of the UI have no extension.
+fn has_extension(path: &str) -> bool {
+    path.rsplit('/').next().is_some_and(|name| name.contains('.'))
+}
+
+//Names like main.3f9a1c2b.js or 787.9c2b1e4f.chunk.css given by the build.
+fn is_hashed(file: &Path) -> bool {
+    file.file_name().and_then(OsStr::to_str).is_some_and(|name| {
+        let parts: Vec<&str> = name.split('.').collect();
+        parts.len() > 2
+            && parts[1..parts.len() - 1]
+                .iter()
+                .any(|part| part.len() >= 8 && part.chars().all(|c| c.is_ascii_hexdigit()))
+    })
+}
+
+//The coding is accepted when it is listed without q=0.
+fn accepts_encoding(req: &HttpRequest, coding: &str) -> bool {
+    req.headers()
+        .get_all(header::ACCEPT_ENCODING)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|item| {
+            let mut params = item.split(';').map(str::trim);
+            params.next().is_some_and(|name| name.eq_ignore_ascii_case(coding))
+                && !params.any(|param| {
+                    param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)
+                })
+        })
+}
+
+//The .br and .gz files next to a file are its precompressed variants, the build can create them with
+//brotli -k and gzip -k.
+fn variant(file: &Path, extension: &str) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+async fn send_file(req: &HttpRequest, file: &Path) -> Result<HttpResponse, ServiceError> {
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or("");
+    let variants = [("br", ".br", ContentEncoding::Brotli), ("gzip", ".gz", ContentEncoding::Gzip)]
+        .map(|(coding, suffix, encoding)| (coding, variant(file, suffix), encoding));
+    let has_variants = variants.iter().any(|(_, path, _)| path.is_file());
+    let (path, encoding) = variants
+        .into_iter()
+        .find(|(coding, path, _)| path.is_file() && accepts_encoding(req, coding))
+        .map_or((file.to_path_buf(), None), |(_, path, encoding)| (path, Some(encoding)));
+
+    let named_file = NamedFile::open_async(&path)
+        .await
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => ServiceError::NotFound("File not found.".into()),
+            _ => ServiceError::ActixError(err.to_string()),
+        })?
+        .set_content_type(actix_files::file_extension_to_mime(extension))
+        .disable_content_disposition();
+    let named_file = match encoding {
+        Some(encoding) => named_file.set_content_encoding(encoding),
+        None => named_file,
+    };
+    let mut response = named_file.into_response(req);
+    let cache_control = if is_hashed(file) { HASHED_CACHE_CONTROL } else { DEFAULT_CACHE_CONTROL };
+    response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    if has_variants {
+        response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use crate::errors::PROBLEM_JSON;
+    use crate::routes::{api_routes, error_handlers, ui_routes};
+    use crate::settings::{ApiSettings, FeatureSettings};
+    use crate::test_utils::test_state;
+    use uuid::Uuid;
+
+    const INDEX_HTML: &str = "<html><body>library-ui</body></html>";
+    const MAIN_JS: &str = "console.log('library-ui');";
+
+    #[actix_rt::test]
+    async fn test_ui() {
+        let shared_data = test_state().await;
+
+        //A build with precompressed variants, next to a file which must not be served.
+        let root = env::temp_dir().join(format!("library-ui-{}", Uuid::new_v4()));
+        let settings = UiSettings {
+            enabled: true,
+            dir: root.join("build"),
+        };
+        fs::create_dir_all(settings.dir.join("static/js")).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+        fs::write(settings.dir.join(".env"), "secret").unwrap();
+        fs::write(settings.index_file(), INDEX_HTML).unwrap();
+        fs::write(settings.dir.join("manifest.json"), "{}").unwrap();
+        fs::write(settings.dir.join("static/js/main.3f9a1c2b.js"), MAIN_JS).unwrap();
+        fs::write(settings.dir.join("static/js/main.3f9a1c2b.js.br"), "brotli").unwrap();
+        fs::write(settings.dir.join("static/js/main.3f9a1c2b.js.gz"), "gzip").unwrap();
+
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .configure(error_handlers)
+            .configure(|cfg| api_routes(cfg, &FeatureSettings::default(), &ApiSettings::default()))
+            .configure(|cfg| ui_routes(cfg, &settings))).await;
+
+        //The routes of the UI are answered with its page.
+        for uri in ["/", "/books", "/books/42/edit"] {
+            let request = test::TestRequest::get().uri(uri).to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::OK, "{}", uri);
+            assert_eq!(http_response.headers().get(header::CONTENT_TYPE).unwrap(), "text/html; charset=utf-8");
+            assert_eq!(http_response.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+            assert!(http_response.headers().get(header::CONTENT_DISPOSITION).is_none());
+            let body = test::read_body(http_response).await;
+            assert_eq!(body, INDEX_HTML);
+        }
+
+        let request = test::TestRequest::get().uri("/manifest.json").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        assert_eq!(http_response.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+        assert!(http_response.headers().get(header::VARY).is_none());
+
+        for (accept_encoding, encoding, body) in [
+            ("gzip, deflate, br", Some("br"), "brotli"),
+            ("gzip, br;q=0", Some("gzip"), "gzip"),
+            ("identity", None, MAIN_JS),
+        ] {
+            let request = test::TestRequest::get()
+                .uri("/static/js/main.3f9a1c2b.js")
+                .insert_header((header::ACCEPT_ENCODING, accept_encoding))
+                .to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::OK);
+            assert_eq!(http_response.headers().get(header::CONTENT_TYPE).unwrap(), "text/javascript");
+            assert_eq!(http_response.headers().get(header::CACHE_CONTROL).unwrap(), HASHED_CACHE_CONTROL);
+            assert_eq!(http_response.headers().get(header::VARY).unwrap(), "accept-encoding");
+            assert_eq!(
+                http_response.headers().get(header::CONTENT_ENCODING).map(|value| value.to_str().unwrap()),
+                encoding
+            );
+            let response_body = test::read_body(http_response).await;
+            assert_eq!(response_body, body);
+        }
+
+        //The missing files, the hidden files and the paths out of the build are not found.
+        for uri in ["/static/js/missing.js", "/.env", "/../secret.txt", "/static/../../secret.txt"] {
+            let request = test::TestRequest::get().uri(uri).to_request();
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+
+        //The API routes are under /api and its unknown routes are not answered with the page.
+        let request = test::TestRequest::get().uri("/api/v1/library/1").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::OK);
+        for request in [
+            test::TestRequest::get().uri("/api/unknown").to_request(),
+            test::TestRequest::get().uri("/api").to_request(),
+            test::TestRequest::post().uri("/books").to_request(),
+        ] {
+            let http_response = test::call_service(&app, request).await;
+            assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(http_response.headers().get(header::CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
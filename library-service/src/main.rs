@@ -4,67 +4,319 @@ mod handlers;
 mod model;
 #[path = "./dal/mod.rs"]
 mod dal;
+mod audit;
+mod auth;
+mod cors;
 mod errors;
+mod metrics;
+mod migrations;
+mod openapi;
+mod preconditions;
+mod rate_limit;
 mod routes;
+mod settings;
+mod shutdown;
 mod state;
+mod telemetry;
+#[cfg(test)]
+mod test_utils;
+mod versioning;
 
-use actix_web::{web, App, HttpServer, http};
-use actix_cors::Cors;
+use actix_web::http::KeepAlive;
+use actix_web::middleware::Condition;
+use actix_web::{web, App, HttpServer};
+use clap::Parser;
+use futures_util::future::{select, Either};
 use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
 use routes::*;
 use state::AppState;
-use std::env;
 use dotenv::dotenv;
 use sqlx::postgres::{PgPoolOptions};
+use metrics::RequestMetrics;
+use migrations::{migration_status, run_migrations, seed_test_data};
+use rate_limit::{MemoryStore, RateLimiter};
+use auth::AuthConfig;
+use cors::CorsProfile;
+use settings::Settings;
+use shutdown::{shutdown_on_signal, InFlightRequests, ShutdownState};
+use telemetry::{init_logging, RequestTracing};
+use tracing::{error, info, warn};
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Apply the pending migrations and exit.
+    #[arg(long)]
+    migrate_only: bool,
+    /// Print the status of the migrations and exit with an error when some are not applied.
+    #[arg(long, conflicts_with_all = ["migrate_only", "seed"])]
+    check_migrations: bool,
+    /// Insert the test data after applying the migrations.
+    #[arg(long)]
+    seed: bool,
+    /// Configuration file (TOML or YAML), library-service.toml or .yaml by default.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Print the configuration with the secrets redacted and exit.
+    #[arg(long)]
+    print_config: bool,
+    /// Address of the server, overrides server.hostname_port.
+    #[arg(long, value_name = "HOST:PORT")]
+    bind: Option<String>,
+    /// Number of worker threads, overrides server.workers.
+    #[arg(long)]
+    workers: Option<usize>,
+    /// Log filter, overrides logging.level.
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+    /// Override any setting, e.g. --set database.max_connections=50.
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String)>,
+}
+
+impl Cli {
+    fn overrides(&self) -> Vec<(String, String)> {
+        let mut overrides = self.overrides.clone();
+        if let Some(bind) = &self.bind {
+            overrides.push(("server.hostname_port".into(), bind.clone()));
+        }
+        if let Some(workers) = self.workers {
+            overrides.push(("server.workers".into(), workers.to_string()));
+        }
+        if let Some(log_level) = &self.log_level {
+            overrides.push(("logging.level".into(), log_level.clone()));
+        }
+        overrides
+    }
+}
+
+fn parse_override(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("expected KEY=VALUE: {:?}", value))
+}
 
 #[actix_rt::main]
 async fn main() -> io::Result<()> {
     dotenv().ok();
+    let cli = Cli::parse();
+
+    let settings = match Settings::load(cli.config.as_deref(), &cli.overrides()) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("🔥 Failed to load the configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let validation = settings.validate();
+    if cli.print_config {
+        match toml::to_string_pretty(&settings.redacted()) {
+            Ok(config) => println!("{}", config),
+            Err(err) => eprintln!("🔥 Failed to print the configuration: {}", err),
+        }
+    }
+    //The logs are configured by the settings, the errors of the settings are printed for the operator instead.
+    if let Err(errors) = validation {
+        eprintln!("🔥 The configuration is invalid:");
+        errors.iter().for_each(|error| eprintln!("  - {}", error));
+        std::process::exit(1);
+    }
+    if cli.print_config {
+        return Ok(());
+    }
+
+    init_logging(&settings.logging);
 
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL is not set in .env file");
+    if settings.cors.profile == CorsProfile::Dev {
+        warn!("the CORS dev profile accepts every origin");
+    }
+
+    let auth_config = match AuthConfig::from_settings(&settings.auth) {
+        Ok(Some(auth_config)) => Some(web::Data::new(auth_config.with_api_keys(settings.features.api_keys))),
+        Ok(None) => {
+            warn!("the authentication is disabled");
+            None
+        }
+        Err(err) => {
+            error!(error = %err, "failed to configure the authentication");
+            std::process::exit(1);
+        }
+    };
 
+    let ssl_acceptor = match settings.server.tls.enabled {
+        true => match settings.server.tls.ssl_acceptor() {
+            Ok(ssl_acceptor) => Some(ssl_acceptor),
+            Err(err) => {
+                error!(error = %err, "failed to configure the TLS");
+                std::process::exit(1);
+            }
+        },
+        false => None,
+    };
+
+    let database = &settings.database;
     let db_pool = match PgPoolOptions::new()
-        .idle_timeout(std::time::Duration::from_secs(30))
-        .max_connections(20)
-        .connect(&database_url)
+        .idle_timeout(Duration::from_secs(database.idle_timeout_secs))
+        .acquire_timeout(Duration::from_secs(database.acquire_timeout_secs))
+        .max_connections(database.max_connections)
+        .min_connections(database.min_connections)
+        .connect(&database.url)
         .await
     {
         Ok(db_pool) => {
-            println!("✅Connection to the database is successful!");
+            info!("connection to the database is successful");
             db_pool
         }
         Err(err) => {
-            println!("🔥 Failed to connect to the database: {:?}", err);
+            error!(error = %err, "failed to connect to the database");
             std::process::exit(1);
         }
     };
 
+    if cli.check_migrations {
+        let status = match migration_status(&db_pool).await {
+            Ok(status) => status,
+            Err(err) => {
+                error!(error = %err, "failed to read the migrations status");
+                std::process::exit(1);
+            }
+        };
+        println!("{:<10}{:<30}status", "version", "description");
+        status.iter().for_each(|migration| {
+            println!("{:<10}{:<30}{}", migration.version, migration.description, migration.state());
+        });
+        std::process::exit(if status.iter().all(|migration| migration.is_up_to_date()) { 0 } else { 1 });
+    }
+
+    match run_migrations(&db_pool).await {
+        Ok(()) => info!("database migrations are up to date"),
+        Err(err) => {
+            error!(error = %err, "failed to apply the database migrations");
+            std::process::exit(1);
+        }
+    }
+
+    if cli.seed {
+        match seed_test_data(&db_pool).await {
+            Ok(()) => info!("test data inserted"),
+            Err(err) => {
+                error!(error = %err, "failed to insert the test data");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if cli.migrate_only {
+        return Ok(());
+    }
+
     let shared_data = web::Data::new(AppState {
         probe: "Probe test ok....".to_string(),
-        db: db_pool,
+        db: db_pool.clone(),
+        trash_retention_days: settings.trash.retention_days,
     });
 
+    //Shared by the workers so a client has the same buckets on every connection.
+    let rate_limiter = match settings.rate_limit.enabled {
+        true => Some(web::Data::new(RateLimiter::new(settings.rate_limit.clone(), Box::<MemoryStore>::default()))),
+        false => {
+            warn!("the rate limiting is disabled");
+            None
+        }
+    };
+
+    let shutdown_state = web::Data::new(ShutdownState::default());
+
+    let serve_ui = settings.ui.enabled && settings.ui.index_file().is_file();
+    if settings.ui.enabled && !serve_ui {
+        warn!(dir = %settings.ui.dir.display(), "the UI is not served, its directory has no index.html");
+    }
+
+    let server_settings = settings.server.clone();
+    let app_shutdown_state = shutdown_state.clone();
     let app = move || {
-//The following section applies only to the development mode of React. Error handling in production mode is done with regular try/catch statements.
-//https://reactjs.org/docs/cross-origin-errors.html
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:3000")
-            .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE"])
-            .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
-            .allowed_header(http::header::CONTENT_TYPE)
-            .max_age(3600);
+        let cors = settings.cors.cors();
+        let features = settings.features.clone();
+        let health = settings.health.clone();
+        let api = settings.api.clone();
         App::new()
             .wrap(cors)
+            .wrap(Condition::new(features.metrics, RequestMetrics))
+            .wrap(RequestTracing {
+                access_log: settings.logging.access_log,
+            })
+            .wrap(InFlightRequests)
             .app_data(shared_data.clone())
+            .app_data(app_shutdown_state.clone())
+            .configure(|cfg| {
+                if let Some(auth_config) = &auth_config {
+                    cfg.app_data(auth_config.clone());
+                }
+                if let Some(rate_limiter) = &rate_limiter {
+                    cfg.app_data(rate_limiter.clone());
+                }
+            })
+            .configure(error_handlers)
             .configure(general_routes)
-            .configure(book_routes)
+            .configure(|cfg| health_routes(cfg, &health))
+            .configure(|cfg| {
+                if features.metrics {
+                    metrics_routes(cfg);
+                }
+            })
+            .configure(|cfg| {
+                if features.openapi {
+                    openapi_routes(cfg);
+                }
+            })
+            .configure(|cfg| api_routes(cfg, &features, &api))
+            .configure(|cfg| {
+                if serve_ui {
+                    ui_routes(cfg, &settings.ui);
+                }
+            })
     };
 
-    let hostname_port = env::var("SERVER_HOSTNAME_PORT").expect("SERVER_HOSTNAME_PORT is not set in .env file");
-    println!("Http Server running on host:port = {:?}", hostname_port);
-    HttpServer::new(app)
-        .bind(hostname_port)?
-        .run()
-        .await
-}
\ No newline at end of file
+    let mut server = HttpServer::new(app)
+        .keep_alive(match server_settings.keep_alive_secs {
+            0 => KeepAlive::Disabled,
+            secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+        })
+        .shutdown_timeout(server_settings.shutdown_timeout_secs)
+        .disable_signals();
+    if let Some(workers) = server_settings.workers {
+        server = server.workers(workers);
+    }
+    let hostname_port = server_settings.hostname_port;
+    server = match ssl_acceptor {
+        Some(ssl_acceptor) => {
+            info!(hostname_port = %hostname_port, "https server running");
+            server.bind_openssl(hostname_port, ssl_acceptor)?
+        }
+        None => {
+            info!(hostname_port = %hostname_port, "http server running");
+            server.bind(hostname_port)?
+        }
+    };
+    let server = server.run();
+    let shutdown = shutdown_on_signal(
+        server.handle(),
+        shutdown_state.clone(),
+        Duration::from_secs(server_settings.shutdown_delay_secs),
+    );
+    if let Either::Left((result, _)) = select(server, Box::pin(shutdown)).await {
+        result?;
+    }
+
+    let summary = shutdown_state.summary();
+    if summary.aborted > 0 {
+        warn!(drained = summary.drained, aborted = summary.aborted, "requests aborted after the shutdown timeout");
+    }
+    db_pool.close().await;
+    info!(drained = summary.drained, aborted = summary.aborted, "shutdown completed, the database pool is closed");
+    Ok(())
+}
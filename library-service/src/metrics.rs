@@ -0,0 +1,183 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use once_cell::sync::Lazy;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use sqlx::postgres::PgPool;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+//Label of the requests which did not match a route, the paths are not used as labels so a scan of random
+//paths can not create new series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+//Metrics of the service in the Prometheus text format, registered in their own registry.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_query_duration: HistogramVec,
+    db_queries_in_flight: IntGauge,
+    db_pool_connections: IntGaugeVec,
+    db_pool_max_connections: IntGauge,
+    db_pool_waiters: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by method, route and status code."),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time to answer the HTTP requests by method and route."),
+            &["method", "route"],
+        )
+        .unwrap();
+        let db_query_duration = HistogramVec::new(
+            HistogramOpts::new("db_query_duration_seconds", "Duration of the data access functions, waiting for a connection included.")
+                .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+            &["function"],
+        )
+        .unwrap();
+        let db_queries_in_flight = IntGauge::new("db_queries_in_flight", "Data access functions running.").unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Connections of the database pool by state (idle, in_use)."),
+            &["state"],
+        )
+        .unwrap();
+        let db_pool_max_connections = IntGauge::new("db_pool_max_connections", "Size limit of the database pool.").unwrap();
+        let db_pool_waiters = IntGauge::new(
+            "db_pool_waiters",
+            "Data access functions waiting for a connection, estimated from the functions running and the connections in use.",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(db_query_duration.clone())).unwrap();
+        registry.register(Box::new(db_queries_in_flight.clone())).unwrap();
+        registry.register(Box::new(db_pool_connections.clone())).unwrap();
+        registry.register(Box::new(db_pool_max_connections.clone())).unwrap();
+        registry.register(Box::new(db_pool_waiters.clone())).unwrap();
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_query_duration,
+            db_queries_in_flight,
+            db_pool_connections,
+            db_pool_max_connections,
+            db_pool_waiters,
+        }
+    }
+
+    //The gauges of the pool are read when the metrics are scraped.
+    pub fn render(&self, pool: &PgPool) -> Result<String, String> {
+        let size = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+        self.db_pool_connections.with_label_values(&["idle"]).set(idle);
+        self.db_pool_connections.with_label_values(&["in_use"]).set(size - idle);
+        self.db_pool_max_connections.set(pool.options().get_max_connections() as i64);
+        self.db_pool_waiters.set((self.db_queries_in_flight.get() - (size - idle)).max(0));
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|err| err.to_string())?;
+        String::from_utf8(buffer).map_err(|err| err.to_string())
+    }
+}
+
+//Measures a data access function until it is dropped, created at the start of the functions which use the pool.
+pub struct QueryTimer {
+    function: &'static str,
+    start: Instant,
+}
+
+impl QueryTimer {
+    pub fn start(function: &'static str) -> Self {
+        METRICS.db_queries_in_flight.inc();
+        QueryTimer {
+            function,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        METRICS.db_queries_in_flight.dec();
+        METRICS
+            .db_query_duration
+            .with_label_values(&[self.function])
+            .observe(self.start.elapsed().as_secs_f64());
+    }
+}
+
+//Counts the requests and measures their duration, labelled with the pattern of the matched route.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let start = Instant::now();
+        let method = req.method().to_string();
+        Box::pin(async move {
+            let result = service.call(req).await;
+            let (route, status) = match &result {
+                Ok(response) => (
+                    response.request().match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.into()),
+                    response.status(),
+                ),
+                Err(err) => (UNMATCHED_ROUTE.into(), err.as_response_error().status_code()),
+            };
+            METRICS
+                .http_requests
+                .with_label_values(&[&method, &route, status.as_str()])
+                .inc();
+            METRICS
+                .http_request_duration
+                .with_label_values(&[&method, &route])
+                .observe(start.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
@@ -0,0 +1,59 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::PgPool;
+use sqlx::Executor;
+
+//The schema scripts are embedded in the binary and the applied versions are recorded in the table _sqlx_migrations.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./src/model/sql_scripts");
+
+const TEST_DATA: &str = include_str!("./model/sql_seeds/1_testData.sql");
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    pub checksum_matches: bool,
+}
+
+impl MigrationStatus {
+    pub fn is_up_to_date(&self) -> bool {
+        self.applied && self.checksum_matches
+    }
+
+    pub fn state(&self) -> &'static str {
+        match (self.applied, self.checksum_matches) {
+            (false, _) => "pending",
+            (true, true) => "applied",
+            (true, false) => "checksum mismatch",
+        }
+    }
+}
+
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied_migrations = conn.list_applied_migrations().await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| {
+            let applied = applied_migrations
+                .iter()
+                .find(|applied| applied.version == migration.version);
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied: applied.is_some(),
+                checksum_matches: applied.is_none_or(|applied| applied.checksum == migration.checksum),
+            }
+        })
+        .collect())
+}
+
+pub async fn seed_test_data(pool: &PgPool) -> Result<(), sqlx::Error> {
+    pool.execute(TEST_DATA).await.map(|_| ())
+}
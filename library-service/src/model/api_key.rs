@@ -0,0 +1,66 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+use crate::auth::Role;
+use crate::model::book::{no_control_characters, trimmed, MAX_TEXT_LENGTH};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+const API_KEY_PREFIX: &str = "lk_";
+const API_KEY_BYTES: usize = 32;
+//Characters of the key kept in clear text to recognize it in the list of keys.
+const API_KEY_VISIBLE_LENGTH: usize = 11;
+
+//Body of POST /admin/api-keys, the scopes are the roles granted to the key.
+#[derive(Deserialize, Serialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewApiKey {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(
+        length(min = 1, max = "MAX_TEXT_LENGTH", message = "The name must have between 1 and 140 characters."),
+        custom = "no_control_characters"
+    )]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required."))]
+    pub scopes: Vec<Role>,
+    #[serde(default)]
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+//The key itself is never stored, only its SHA-256 hash.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub request_count: i64,
+    pub created_at: NaiveDateTime,
+}
+
+//Response of the creation and the rotation of a key, the only time the key is sent to the client.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+//New random key with the prefix shown in the list of keys.
+pub fn generate_api_key() -> Result<(String, String), openssl::error::ErrorStack> {
+    let mut bytes = [0u8; API_KEY_BYTES];
+    openssl::rand::rand_bytes(&mut bytes)?;
+    let key = format!("{}{}", API_KEY_PREFIX, to_hex(&bytes));
+    let prefix = key[..API_KEY_VISIBLE_LENGTH].to_string();
+    Ok((key, prefix))
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    to_hex(&openssl::sha::sha256(key.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
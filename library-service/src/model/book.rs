@@ -1,28 +1,377 @@
-use actix_web::web;
 use chrono::NaiveDateTime;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
 pub struct Book {
     pub id: i32,
     pub title: String,
     pub author: String,
     pub posted_time: Option<NaiveDateTime>,
+    //Incremented on every update and sent in the ETag header.
+    #[serde(skip)]
+    pub version: i32,
 }
 
-impl From<web::Json<Book>> for Book {
-    fn from(book: web::Json<Book>) -> Self {
-        Book {
-            id: book.id,
-            title: book.title.clone(),
-            author: book.author.clone(),
-            posted_time: book.posted_time,
+impl PartialEq for Book {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+//Same limit as the varchar columns of the table books.
+pub const MAX_TEXT_LENGTH: u64 = 140;
+
+//Payload of POST /library/, the id and the posted_time are assigned by the database.
+#[derive(Deserialize, Serialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewBook {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(custom = "valid_title")]
+    pub title: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(custom = "valid_author")]
+    pub author: String,
+}
+
+//Payload of PUT /library/{id}, a full replacement of the book. The id is optional but when present it must
+//match the id of the path.
+#[derive(Deserialize, Serialize, Debug, Clone, Validate, ToSchema)]
+pub struct UpdateBook {
+    #[serde(default)]
+    pub id: Option<i32>,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(custom = "valid_title")]
+    pub title: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(custom = "valid_author")]
+    pub author: String,
+}
+
+//Payload of PATCH /library/{id} as a JSON Merge Patch (RFC 7396), the absent members are left unchanged.
+//A null member would remove it, which is rejected because the title and the author are required.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Validate, ToSchema)]
+pub struct BookPatch {
+    #[serde(default, deserialize_with = "present_trimmed")]
+    #[validate(custom = "valid_title")]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "present_trimmed")]
+    #[validate(custom = "valid_author")]
+    pub author: Option<String>,
+}
+
+pub const BULK_CHUNK_SIZE: usize = 1000;
+
+//Row of POST /library/bulk. Without id a new book is created, with the id of an existing book the row conflicts
+//unless the conflicts are updated, and with an unknown id the book is created with that id.
+#[derive(Deserialize, Serialize, Debug, Clone, Validate, ToSchema)]
+pub struct BulkBook {
+    #[serde(default)]
+    pub id: Option<i32>,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(custom = "valid_title")]
+    pub title: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(custom = "valid_author")]
+    pub author: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BulkMode {
+    #[default]
+    AllOrNothing,
+    BestEffort,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Error,
+    Update,
+}
+
+//Query string accepted by POST /library/bulk.
+#[derive(Deserialize, Debug, Clone, Copy, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BulkOptions {
+    #[serde(default)]
+    pub mode: BulkMode,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkRowStatus {
+    Created,
+    Updated,
+    Conflict,
+    Invalid,
+    //Valid row not applied because another row failed in the all-or-nothing mode.
+    RolledBack,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct BulkRowResult {
+    pub index: usize,
+    pub status: BulkRowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    //Line of the uploaded file, only set by POST /library/import.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct BulkReport {
+    pub mode: BulkMode,
+    pub committed: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub failed: usize,
+    pub results: Vec<BulkRowResult>,
+}
+
+impl BulkReport {
+    pub fn new(mode: BulkMode, committed: bool, mut results: Vec<BulkRowResult>) -> Self {
+        results.sort_by_key(|result| result.index);
+        let count = |status: BulkRowStatus| results.iter().filter(|result| result.status == status).count();
+        BulkReport {
+            mode,
+            committed,
+            created: count(BulkRowStatus::Created),
+            updated: count(BulkRowStatus::Updated),
+            failed: count(BulkRowStatus::Conflict) + count(BulkRowStatus::Invalid),
+            results,
         }
     }
 }
 
-impl PartialEq for Book {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+pub const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+//A hundred years, the cutoff of the purge is computed from it.
+pub const MAX_TRASH_RETENTION_DAYS: i64 = 36_500;
+
+//A deleted book stays in the trash until it is restored or purged.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct TrashedBook {
+    #[serde(flatten)]
+    pub book: Book,
+    pub deleted_at: NaiveDateTime,
+}
+
+//Query string accepted by GET /library/trash, the books deleted last come first.
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrashQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl TrashQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
     }
-}
\ No newline at end of file
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct TrashPage {
+    pub items: Vec<TrashedBook>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct PurgeReport {
+    pub purged: u64,
+    pub retention_days: i64,
+}
+
+//Upload limit of POST /library/import, the JSON bodies keep the default limit of actix-web.
+pub const IMPORT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "books.json",
+            ExportFormat::Csv => "books.csv",
+            ExportFormat::Ndjson => "books.ndjson",
+        }
+    }
+}
+
+//Query string accepted by GET /library/export.
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+pub fn trimmed<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|value| value.trim().to_string())
+}
+
+fn present_trimmed<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => Ok(Some(value.trim().to_string())),
+        None => Err(de::Error::custom("the member is required and can not be removed with null")),
+    }
+}
+
+//Rules of the title and the author, shared by every payload of a book.
+pub fn valid_title(value: &str) -> Result<(), ValidationError> {
+    valid_book_text(value, "The title must have between 1 and 140 characters.")
+}
+
+pub fn valid_author(value: &str) -> Result<(), ValidationError> {
+    valid_book_text(value, "The author must have between 1 and 140 characters.")
+}
+
+fn valid_book_text(value: &str, length_message: &'static str) -> Result<(), ValidationError> {
+    let length = value.chars().count() as u64;
+    if !(1..=MAX_TEXT_LENGTH).contains(&length) {
+        let mut error = ValidationError::new("length");
+        error.message = Some(length_message.into());
+        return Err(error);
+    }
+    no_control_characters(value)
+}
+
+pub fn no_control_characters(value: &str) -> Result<(), ValidationError> {
+    if value.chars().any(char::is_control) {
+        let mut error = ValidationError::new("control_characters");
+        error.message = Some("Control characters are not allowed.".into());
+        return Err(error);
+    }
+    Ok(())
+}
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 50;
+pub const MAX_PAGE_LIMIT: i64 = 500;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSort {
+    #[default]
+    Id,
+    Title,
+    Author,
+    PostedTime,
+}
+
+impl BookSort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            BookSort::Id => "id",
+            BookSort::Title => "title",
+            BookSort::Author => "author",
+            BookSort::PostedTime => "COALESCE(record_timestamp, '-infinity')",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+//Query string accepted by GET /library. The cursor is the id of the last book of the previous page,
+//when it is present the offset is ignored.
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BookQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<i32>,
+    pub author: Option<String>,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub sort: BookSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl BookQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        match self.cursor {
+            Some(_) => 0,
+            None => self.offset.unwrap_or(0).max(0),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct BookPage {
+    pub items: Vec<Book>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next_cursor: Option<i32>,
+}
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+
+//Query string accepted by GET /library/search, q uses the web search syntax of Postgres
+//("quoted phrases", OR and -excluded words).
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BookSearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+impl BookSearchQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+}
+
+//The highlights are safe HTML: the title and the author are escaped and the matched words are wrapped in
+//<mark></mark> tags.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct BookSearchResult {
+    #[serde(flatten)]
+    pub book: Book,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub author_highlight: String,
+}
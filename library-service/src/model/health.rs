@@ -0,0 +1,36 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+//Body of the health routes, the service is up when all its components are up.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+impl HealthReport {
+    pub fn new(components: BTreeMap<&'static str, ComponentHealth>) -> Self {
+        let status = match components.values().all(|component| component.status == HealthStatus::Up) {
+            true => HealthStatus::Up,
+            false => HealthStatus::Down,
+        };
+        HealthReport { status, components }
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use serde_json::Value;
+use crate::model::book::Book;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Purge,
+    //Update that brings back the title and author of a past revision.
+    Revert,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+            AuditAction::Purge => "purge",
+            AuditAction::Revert => "revert",
+        }
+    }
+}
+
+//Change of a book waiting to be appended to its history, before is None for a new book and after is
+//None for a purged book.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub book_id: i32,
+    pub action: AuditAction,
+    pub version: i32,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl HistoryEntry {
+    pub fn new(action: AuditAction, before: Option<&Book>, after: &Book) -> Self {
+        HistoryEntry {
+            book_id: after.id,
+            action,
+            version: after.version,
+            before: before.map(snapshot),
+            after: Some(snapshot(after)),
+        }
+    }
+
+    pub fn purged(book: &Book) -> Self {
+        HistoryEntry {
+            book_id: book.id,
+            action: AuditAction::Purge,
+            version: book.version,
+            before: Some(snapshot(book)),
+            after: None,
+        }
+    }
+}
+
+fn snapshot(book: &Book) -> Value {
+    serde_json::to_value(book).unwrap_or(Value::Null)
+}
+
+//One entry of GET /library/{id}/history, the revision is the id used to revert the book to its after snapshot.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct BookRevision {
+    pub revision: i64,
+    pub book_id: i32,
+    pub action: String,
+    pub actor: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub version: i32,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub recorded_at: NaiveDateTime,
+}
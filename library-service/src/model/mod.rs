@@ -1 +1,4 @@
-pub mod book;
\ No newline at end of file
+pub mod api_key;
+pub mod book;
+pub mod health;
+pub mod history;
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE public.books
+    ADD COLUMN IF NOT EXISTS search tsvector
+        GENERATED ALWAYS AS (setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', author), 'B')) STORED;
+
+CREATE INDEX IF NOT EXISTS books_search_idx ON public.books USING GIN (search);
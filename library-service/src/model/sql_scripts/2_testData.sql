@@ -1,5 +0,0 @@
-INSERT INTO public.books(id, title, author)	VALUES (1, 'The Raven', 'Edgar Allan Poe');
-INSERT INTO public.books(id, title, author)	VALUES (2, 'The Tomb', 'H. P. Lovecraft');
-INSERT INTO public.books(id, title, author)	VALUES (3, 'Moby-Dick', 'Herman Melville');
-INSERT INTO public.books(id, title, author)	VALUES (4, 'A Tale of Two Cities', 'Charles Dickens');
-INSERT INTO public.books(id, title, author)	VALUES (5, 'The Art of War', 'Sun Tzu');
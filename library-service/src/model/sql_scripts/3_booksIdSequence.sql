@@ -0,0 +1 @@
+SELECT setval(pg_get_serial_sequence('public.books', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM public.books;
@@ -0,0 +1,3 @@
+ALTER TABLE public.books ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP NULL;
+
+CREATE INDEX IF NOT EXISTS books_deleted_at_idx ON public.books (deleted_at) WHERE deleted_at IS NOT NULL;
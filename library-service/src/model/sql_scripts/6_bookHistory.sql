@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS public.book_history
+(
+    id bigserial primary key,
+    book_id integer not null,
+    action varchar(20) not null,
+    actor varchar(140) not null,
+    request_id varchar(140),
+    version integer not null,
+    before jsonb,
+    after jsonb,
+    record_timestamp TIMESTAMP not null default now()
+);
+
+CREATE INDEX IF NOT EXISTS book_history_book_id_idx ON public.book_history (book_id, id);
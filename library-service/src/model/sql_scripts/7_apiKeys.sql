@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS public.api_keys
+(
+    id serial primary key,
+    name varchar(140) not null unique,
+    prefix varchar(16) not null,
+    key_hash char(64) not null unique,
+    scopes text[] not null,
+    expires_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    last_used_at TIMESTAMP,
+    request_count bigint not null default 0,
+    record_timestamp TIMESTAMP not null default now()
+);
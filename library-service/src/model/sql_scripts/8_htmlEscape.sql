@@ -0,0 +1,4 @@
+-- The highlights of the search are built on the escaped title and author, only their <mark> tags are HTML.
+CREATE OR REPLACE FUNCTION public.html_escape(value text) RETURNS text
+    LANGUAGE sql IMMUTABLE STRICT
+    AS $$ SELECT replace(replace(replace(replace(replace(value, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;') $$;
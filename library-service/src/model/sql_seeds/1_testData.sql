@@ -0,0 +1,7 @@
+INSERT INTO public.books(id, title, author)	VALUES (1, 'The Raven', 'Edgar Allan Poe') ON CONFLICT (id) DO NOTHING;
+INSERT INTO public.books(id, title, author)	VALUES (2, 'The Tomb', 'H. P. Lovecraft') ON CONFLICT (id) DO NOTHING;
+INSERT INTO public.books(id, title, author)	VALUES (3, 'Moby-Dick', 'Herman Melville') ON CONFLICT (id) DO NOTHING;
+INSERT INTO public.books(id, title, author)	VALUES (4, 'A Tale of Two Cities', 'Charles Dickens') ON CONFLICT (id) DO NOTHING;
+INSERT INTO public.books(id, title, author)	VALUES (5, 'The Art of War', 'Sun Tzu') ON CONFLICT (id) DO NOTHING;
+
+SELECT setval(pg_get_serial_sequence('public.books', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM public.books;
\ No newline at end of file
@@ -0,0 +1,78 @@
+use utoipa::openapi::security::{ApiKey as ApiKeyLocation, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use crate::auth::Role;
+use crate::errors::{FieldError, ProblemDetails};
+use crate::handlers;
+use crate::model::api_key::{ApiKey, IssuedApiKey, NewApiKey};
+use crate::model::book::{
+    Book, BookPage, BookPatch, BookSearchResult, BookSort, BulkBook, BulkMode, BulkReport, BulkRowResult,
+    BulkRowStatus, ConflictPolicy, ExportFormat, NewBook, PurgeReport, SortOrder, TrashPage, TrashedBook, UpdateBook,
+};
+use crate::model::health::{ComponentHealth, HealthReport, HealthStatus};
+use crate::model::history::BookRevision;
+
+//Names of the security schemes given in the security of the paths.
+const BEARER_SCHEME: &str = "bearer";
+const API_KEY_SCHEME: &str = "api_key";
+
+//OpenAPI 3 document of the routes, served at /openapi.json. Every route of routes.rs must be listed in the paths,
+//the test of the document fails otherwise.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        handlers::book::post_add_book,
+        handlers::book::post_bulk_insert,
+        handlers::book::post_import_books,
+        handlers::book::get_export_books,
+        handlers::book::get_books,
+        handlers::book::search_books,
+        handlers::book::get_trash,
+        handlers::book::post_purge_trash,
+        handlers::book::post_restore_book_by_id,
+        handlers::book::get_book_history,
+        handlers::book::post_revert_book_to_revision,
+        handlers::book::get_book_by_id,
+        handlers::book::put_book_by_id,
+        handlers::book::patch_book_by_id,
+        handlers::book::delete_book_by_id,
+        handlers::api_key::post_api_key,
+        handlers::api_key::get_api_keys,
+        handlers::api_key::post_rotate_api_key,
+        handlers::api_key::delete_api_key,
+        handlers::health::get_live,
+        handlers::health::get_ready,
+        handlers::metrics::get_metrics,
+        handlers::default::get_probe,
+    ),
+    components(schemas(
+        Book, NewBook, UpdateBook, BookPatch, BookPage, BookSearchResult, BookSort, SortOrder,
+        BulkBook, BulkMode, ConflictPolicy, BulkReport, BulkRowResult, BulkRowStatus, ExportFormat,
+        TrashedBook, TrashPage, PurgeReport, BookRevision,
+        NewApiKey, ApiKey, IssuedApiKey, Role,
+        HealthReport, ComponentHealth, HealthStatus,
+        ProblemDetails, FieldError,
+    )),
+    tags(
+        (name = "books", description = "Books of the library, their trash and their history."),
+        (name = "api-keys", description = "API keys of the machine clients, managed by the admins."),
+        (name = "operations", description = "Health checks, metrics and probe, not authenticated."),
+    ),
+    modifiers(&SecuritySchemes)
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            BEARER_SCHEME,
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+        components.add_security_scheme(
+            API_KEY_SCHEME,
+            SecurityScheme::ApiKey(ApiKeyLocation::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use actix_web::dev::Payload;
+use actix_web::http::header::{EntityTag, Header, IfMatch, IfNoneMatch};
+use actix_web::{FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+use crate::errors::ServiceError;
+use crate::model::book::Book;
+
+//Strong entity tag of a book, its version between quotes.
+pub fn book_etag(book: &Book) -> EntityTag {
+    EntityTag::new_strong(book.version.to_string())
+}
+
+//Conditional request headers (RFC 7232) used to detect lost updates on a book.
+#[derive(Debug, Clone, Default)]
+pub struct Preconditions {
+    if_match: Option<IfMatch>,
+    if_none_match: Option<IfNoneMatch>,
+}
+
+impl Preconditions {
+    //Versions accepted by the If-Match header, None when the request is unconditional.
+    //If-Match uses the strong comparison, so weak entity tags never match.
+    pub fn expected_versions(&self) -> Option<Vec<i32>> {
+        match &self.if_match {
+            Some(IfMatch::Items(tags)) => Some(tags
+                .iter()
+                .filter(|tag| !tag.weak)
+                .filter_map(|tag| tag.tag().parse().ok())
+                .collect()),
+            Some(IfMatch::Any) | None => None,
+        }
+    }
+
+    //If-None-Match uses the weak comparison.
+    pub fn is_not_modified(&self, etag: &EntityTag) -> bool {
+        match &self.if_none_match {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+            None => false,
+        }
+    }
+
+    fn parse(req: &HttpRequest) -> Result<Self, ServiceError> {
+        let if_match = match req.headers().contains_key(IfMatch::name()) {
+            true => Some(IfMatch::parse(req)
+                .map_err(|_| ServiceError::BadRequest("The If-Match header is invalid.".into()))?),
+            false => None,
+        };
+        let if_none_match = match req.headers().contains_key(IfNoneMatch::name()) {
+            true => Some(IfNoneMatch::parse(req)
+                .map_err(|_| ServiceError::BadRequest("The If-None-Match header is invalid.".into()))?),
+            false => None,
+        };
+        Ok(Preconditions {
+            if_match,
+            if_none_match,
+        })
+    }
+}
+
+impl FromRequest for Preconditions {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Preconditions::parse(req))
+    }
+}
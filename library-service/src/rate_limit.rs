@@ -0,0 +1,276 @@
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::{web, Error, HttpMessage};
+use futures_util::future::{BoxFuture, LocalBoxFuture};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::auth::Identity;
+use crate::errors::ServiceError;
+use crate::settings::{Quota, RateLimitSettings};
+
+//The full buckets are dropped at this interval, a full bucket is the same as no bucket.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+//Most buckets kept by MemoryStore, the half updated the longest time ago is dropped when it is reached.
+pub const MAX_BUCKETS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Read,
+    Write,
+    Bulk,
+    Export,
+    Admin,
+}
+
+impl RouteGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Read => "read",
+            RouteGroup::Write => "write",
+            RouteGroup::Bulk => "bulk",
+            RouteGroup::Export => "export",
+            RouteGroup::Admin => "admin",
+        }
+    }
+}
+
+//Result of taking a token, sent in the RateLimit-* headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    //Seconds until the bucket is full again.
+    pub reset_secs: u64,
+    //Seconds until the next token, 0 when a token was taken.
+    pub retry_after_secs: u64,
+}
+
+impl RateLimitDecision {
+    fn insert_headers(&self, headers: &mut HeaderMap) {
+        for (name, value) in [
+            ("ratelimit-limit", self.limit as u64),
+            ("ratelimit-remaining", self.remaining as u64),
+            ("ratelimit-reset", self.reset_secs),
+        ] {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(self.retry_after_secs));
+        }
+    }
+}
+
+//Storage of the token buckets, the buckets are kept in memory by MemoryStore and a store shared by the
+//replicas of the service can implement this trait.
+pub trait RateLimitStore: Send + Sync {
+    fn take<'a>(&'a self, key: &'a str, quota: Quota) -> BoxFuture<'a, Result<RateLimitDecision, ServiceError>>;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    //When the bucket is full again, it can be dropped from then on.
+    full_at: Instant,
+}
+
+#[derive(Default)]
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    pruned_at: Option<Instant>,
+}
+
+impl Buckets {
+    //The buckets are pruned once per interval, or when there are too many, so a request does not go through
+    //every bucket.
+    fn prune(&mut self, now: Instant) {
+        let recently_pruned = self
+            .pruned_at
+            .is_some_and(|pruned_at| now.saturating_duration_since(pruned_at) < PRUNE_INTERVAL);
+        if recently_pruned && self.buckets.len() < MAX_BUCKETS {
+            return;
+        }
+        self.pruned_at = Some(now);
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+        if self.buckets.len() >= MAX_BUCKETS {
+            let mut updated: Vec<Instant> = self.buckets.values().map(|bucket| bucket.updated).collect();
+            let middle = updated.len() / 2;
+            let median = *updated.select_nth_unstable(middle).1;
+            self.buckets.retain(|_, bucket| bucket.updated > median);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<Buckets>,
+}
+
+impl MemoryStore {
+    pub fn take_at(&self, key: &str, quota: Quota, now: Instant) -> RateLimitDecision {
+        let capacity = quota.burst as f64;
+        let tokens_per_sec = quota.per_minute as f64 / 60.0;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        buckets.prune(now);
+        let bucket = buckets
+            .buckets
+            .entry(key.to_string())
+            .or_insert(Bucket { tokens: capacity, updated: now, full_at: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * tokens_per_sec).min(capacity);
+        bucket.updated = now;
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let reset_secs = ((capacity - bucket.tokens) / tokens_per_sec).ceil() as u64;
+        bucket.full_at = now + Duration::from_secs(reset_secs);
+        RateLimitDecision {
+            allowed,
+            limit: quota.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs,
+            retry_after_secs: if allowed { 0 } else { ((1.0 - bucket.tokens) / tokens_per_sec).ceil().max(1.0) as u64 },
+        }
+    }
+}
+
+impl RateLimitStore for MemoryStore {
+    fn take<'a>(&'a self, key: &'a str, quota: Quota) -> BoxFuture<'a, Result<RateLimitDecision, ServiceError>> {
+        Box::pin(ready(Ok(self.take_at(key, quota, Instant::now()))))
+    }
+}
+
+//Quotas and store of the RateLimit middleware, registered in the App. The requests are not limited when it is
+//not registered.
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    store: Box<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings, store: Box<dyn RateLimitStore>) -> Self {
+        RateLimiter { settings, store }
+    }
+
+    fn quota(&self, group: RouteGroup) -> Quota {
+        match group {
+            RouteGroup::Read => self.settings.read,
+            RouteGroup::Write => self.settings.write,
+            RouteGroup::Bulk => self.settings.bulk,
+            RouteGroup::Export => self.settings.export,
+            RouteGroup::Admin => self.settings.admin,
+        }
+    }
+
+    //The Identity is added by the Authentication middleware, which must run before.
+    fn client(&self, req: &ServiceRequest) -> String {
+        if let Some(identity) = req.extensions().get::<Identity>() {
+            return format!("subject:{}", identity.subject);
+        }
+        self.ip(req)
+    }
+
+    fn ip(&self, req: &ServiceRequest) -> String {
+        let connection_info = req.connection_info();
+        let ip = match self.settings.trust_forwarded_for {
+            true => connection_info.realip_remote_addr(),
+            false => connection_info.peer_addr(),
+        };
+        format!("ip:{}", ip.unwrap_or("unknown"))
+    }
+
+    //Bucket and quota of the request.
+    fn bucket(&self, limit: RateLimit, req: &ServiceRequest) -> (String, Quota) {
+        match limit {
+            RateLimit::Group(group) => (format!("{}:{}", group.as_str(), self.client(req)), self.quota(group)),
+            RateLimit::Ip => (self.ip(req), self.settings.ip),
+        }
+    }
+}
+
+//Takes a token from a bucket, 429 Too Many Requests when it is empty. RateLimit::Group is bound to the routes and
+//limits their client in their group. RateLimit::Ip wraps the scopes outside the Authentication middleware so the
+//requests without credentials or with invalid ones are also limited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimit {
+    Group(RouteGroup),
+    Ip,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware { service: Rc::new(service), limit: *self }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limit: RateLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let limit = self.limit;
+        Box::pin(async move {
+            let limiter = match req.app_data::<web::Data<RateLimiter>>() {
+                Some(limiter) => limiter.clone(),
+                None => return service.call(req).await.map(ServiceResponse::map_into_left_body),
+            };
+            let (key, quota) = limiter.bucket(limit, &req);
+            let decision = match limiter.store.take(&key, quota).await {
+                Ok(decision) => decision,
+                Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+            };
+            let mut response = if decision.allowed {
+                service.call(req).await?.map_into_left_body()
+            } else {
+                let err = ServiceError::TooManyRequests(match limit {
+                    RateLimit::Group(group) => format!(
+                        "Too many {} requests, try again in {} seconds.",
+                        group.as_str(),
+                        decision.retry_after_secs
+                    ),
+                    RateLimit::Ip => format!(
+                        "Too many requests from this address, try again in {} seconds.",
+                        decision.retry_after_secs
+                    ),
+                });
+                req.error_response(err).map_into_right_body()
+            };
+            //The headers of the bucket of the route are kept, they are the ones the client can act on.
+            if !decision.allowed || !response.headers().contains_key("ratelimit-limit") {
+                decision.insert_headers(response.headers_mut());
+            }
+            Ok(response)
+        })
+    }
+}
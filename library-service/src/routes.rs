@@ -1,19 +1,172 @@
-use actix_web::web;
+use actix_web::{web, Route};
+use crate::auth::{Authentication, RequireRole, Role};
+use crate::handlers::api_key::*;
 use crate::handlers::book::*;
 use crate::handlers::default::*;
+use crate::handlers::health::*;
+use crate::handlers::metrics::*;
+use crate::handlers::openapi::*;
+use crate::handlers::ui::*;
+use crate::model::book::IMPORT_MAX_BYTES;
+use crate::rate_limit::{RateLimit, RouteGroup};
+use crate::errors::{json_error_handler, path_error_handler, query_error_handler};
+use crate::settings::{ApiSettings, FeatureSettings, HealthSettings, UiSettings};
+use crate::versioning::{AcceptVersion, ApiVersion, Deprecated};
+
+//Prefix of the API routes, the other paths are the routes of the UI.
+pub const API_PREFIX: &str = "/api";
+
+pub fn error_handlers(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(json_error_handler))
+        .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+        .app_data(web::PathConfig::default().error_handler(path_error_handler));
+}
 
 pub fn general_routes(cfg: &mut web::ServiceConfig) {
     cfg.route("/probe", web::get().to(get_probe));
 }
 
+//Not authenticated, scraped by Prometheus.
+pub fn metrics_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/metrics", web::get().to(get_metrics));
+}
+
+//Not authenticated, they are called by the Kubernetes probes.
+pub fn health_routes(cfg: &mut web::ServiceConfig, settings: &HealthSettings) {
+    cfg.service(
+        web::scope("/health")
+            .app_data(web::Data::new(settings.clone()))
+            .route("/live", web::get().to(get_live))
+            .route("/ready", web::get().to(get_ready)),
+    );
+}
+
+//Not authenticated, the document of the API and its page.
+pub fn openapi_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/openapi.json", web::get().to(get_openapi))
+        .route("/docs", web::get().to(get_api_docs));
+}
+
+//The routes of the disabled features are kept so they answer 404 instead of matching another route.
+fn feature(enabled: bool, route: Route) -> Route {
+    if enabled {
+        route
+    } else {
+        route.to(feature_disabled)
+    }
+}
+
+//The route is reserved to the role and limited in the group, its caller is authenticated by the Authentication
+//middleware of its scope.
+fn protected(role: Role, group: RouteGroup, route: Route) -> Route {
+    route.wrap(RateLimit::Group(group)).wrap(RequireRole(role))
+}
+
+//Every feature enabled.
+#[cfg(test)]
 pub fn book_routes(cfg: &mut web::ServiceConfig) {
+    book_routes_with_features(cfg, &FeatureSettings::default());
+}
+
+pub fn book_routes_with_features(cfg: &mut web::ServiceConfig, features: &FeatureSettings) {
     cfg.service(
         web::scope("/library")
-            .route("/", web::post().to(post_add_book))
-            .route("/bulk", web::post().to(post_bulk_insert))
-            .route("", web::get().to(get_books))
-            .route("/{id}", web::get().to(get_book_by_id))
-            .route("/{id}", web::put().to(put_book_by_id))
-            .route("/{id}", web::delete().to(delete_book_by_id)),
+            .wrap(Authentication)
+            .wrap(RateLimit::Ip)
+            .route("/", protected(Role::Librarian, RouteGroup::Write, web::post().to(post_add_book)))
+            .route(
+                "/bulk",
+                protected(Role::Admin, RouteGroup::Bulk, feature(features.bulk_import, web::post().to(post_bulk_insert))),
+            )
+            .service(
+                web::resource("/import")
+                    .app_data(web::PayloadConfig::new(IMPORT_MAX_BYTES))
+                    .route(protected(
+                        Role::Admin,
+                        RouteGroup::Bulk,
+                        feature(features.bulk_import, web::post().to(post_import_books)),
+                    )),
+            )
+            .route(
+                "/export",
+                protected(Role::Reader, RouteGroup::Export, feature(features.export, web::get().to(get_export_books))),
+            )
+            .route("", protected(Role::Reader, RouteGroup::Read, web::get().to(get_books)))
+            .route("/search", protected(Role::Reader, RouteGroup::Read, web::get().to(search_books)))
+            .route("/trash", protected(Role::Reader, RouteGroup::Read, web::get().to(get_trash)))
+            .route("/trash/purge", protected(Role::Admin, RouteGroup::Write, web::post().to(post_purge_trash)))
+            .route(
+                "/{id}/restore",
+                protected(Role::Librarian, RouteGroup::Write, web::post().to(post_restore_book_by_id)),
+            )
+            .route("/{id}/history", protected(Role::Reader, RouteGroup::Read, web::get().to(get_book_history)))
+            .route(
+                "/{id}/history/{revision}/revert",
+                protected(Role::Librarian, RouteGroup::Write, web::post().to(post_revert_book_to_revision)),
+            )
+            .route("/{id}", protected(Role::Reader, RouteGroup::Read, web::get().to(get_book_by_id)))
+            .route("/{id}", protected(Role::Librarian, RouteGroup::Write, web::put().to(put_book_by_id)))
+            .route("/{id}", protected(Role::Librarian, RouteGroup::Write, web::patch().to(patch_book_by_id)))
+            .route("/{id}", protected(Role::Admin, RouteGroup::Write, web::delete().to(delete_book_by_id))),
+    );
+}
+
+//The routes of every version under /api/v<number>, with the routes of the legacy version without version as
+//deprecated aliases. The version is chosen by the path, else by the version parameter of the Accept header.
+pub fn api_routes(cfg: &mut web::ServiceConfig, features: &FeatureSettings, settings: &ApiSettings) {
+    cfg.service(
+        web::scope(API_PREFIX)
+            .configure(|cfg| {
+                for version in ApiVersion::ALL {
+                    cfg.service(
+                        web::scope(&version.prefix())
+                            .guard(AcceptVersion { version, default: true })
+                            .configure(|cfg| version_routes(cfg, version, features)),
+                    );
+                }
+                if let Some((deprecated_at, sunset_at)) = settings.legacy_dates() {
+                    for version in ApiVersion::ALL {
+                        cfg.service(
+                            web::scope("")
+                                .guard(AcceptVersion { version, default: version == ApiVersion::LEGACY })
+                                .wrap(Deprecated::new(version, deprecated_at, sunset_at))
+                                .configure(|cfg| version_routes(cfg, version, features)),
+                        );
+                    }
+                }
+            })
+            .default_service(web::to(api_not_found)),
+    );
+}
+
+//The book and admin routes of a version, the admin routes need the API keys feature.
+fn version_routes(cfg: &mut web::ServiceConfig, version: ApiVersion, features: &FeatureSettings) {
+    match version {
+        ApiVersion::V1 => {
+            book_routes_with_features(cfg, features);
+            if features.api_keys {
+                admin_routes(cfg);
+            }
+        }
+    }
+}
+
+//Serves the UI for the requests which match no other route.
+pub fn ui_routes(cfg: &mut web::ServiceConfig, settings: &UiSettings) {
+    cfg.app_data(web::Data::new(settings.clone()))
+        .default_service(web::to(get_ui_file));
+}
+
+pub fn admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .wrap(RateLimit::Group(RouteGroup::Admin))
+            .wrap(RequireRole(Role::Admin))
+            .wrap(Authentication)
+            .wrap(RateLimit::Ip)
+            .route("/api-keys", web::post().to(post_api_key))
+            .route("/api-keys", web::get().to(get_api_keys))
+            .route("/api-keys/{id}/rotate", web::post().to(post_rotate_api_key))
+            .route("/api-keys/{id}", web::delete().to(delete_api_key)),
     );
 }
\ No newline at end of file
@@ -0,0 +1,622 @@
+use chrono::{DateTime, Utc};
+use config::{Config, Environment, File};
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use crate::cors::CorsSettings;
+use crate::model::book::{DEFAULT_TRASH_RETENTION_DAYS, MAX_TRASH_RETENTION_DAYS};
+
+//Name of the configuration file read from the working directory, library-service.toml, .yaml or .yml.
+pub const DEFAULT_CONFIG_NAME: &str = "library-service";
+//The environment variables are LIBRARY__<SECTION>__<KEY>, e.g. LIBRARY__DATABASE__MAX_CONNECTIONS. The double
+//underscore after the prefix keeps the LIBRARY_SERVICE_* variables created by Kubernetes for the service apart.
+pub const ENV_PREFIX: &str = "LIBRARY";
+pub const ENV_SEPARATOR: &str = "__";
+const REDACTED: &str = "***";
+//Parameters of the database URL which carry a secret, e.g. postgres://db/library?password=...
+const SECRET_URL_PARAMETERS: [&str; 4] = ["password", "sslpassword", "secret", "token"];
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+const LIST_KEYS: [&str; 4] = [
+    "cors.allowed_origins",
+    "cors.allowed_methods",
+    "cors.allowed_headers",
+    "cors.exposed_headers",
+];
+
+//Variables read by the previous versions of the service, they are still accepted with a lower priority than the
+//LIBRARY__* variables.
+const LEGACY_VARIABLES: [(&str, &str); 15] = [
+    ("DATABASE_URL", "database.url"),
+    ("SERVER_HOSTNAME_PORT", "server.hostname_port"),
+    ("TRASH_RETENTION_DAYS", "trash.retention_days"),
+    ("AUTH_DISABLED", "auth.disabled"),
+    ("JWT_SECRET", "auth.jwt_secret"),
+    ("JWT_JWKS_FILE", "auth.jwks_file"),
+    ("JWT_ISSUER", "auth.issuer"),
+    ("JWT_AUDIENCE", "auth.audience"),
+    ("CORS_PROFILE", "cors.profile"),
+    ("CORS_ALLOWED_ORIGINS", "cors.allowed_origins"),
+    ("CORS_ALLOWED_METHODS", "cors.allowed_methods"),
+    ("CORS_ALLOWED_HEADERS", "cors.allowed_headers"),
+    ("CORS_EXPOSED_HEADERS", "cors.exposed_headers"),
+    ("CORS_ALLOW_CREDENTIALS", "cors.allow_credentials"),
+    ("CORS_MAX_AGE", "cors.max_age"),
+];
+
+//Settings of the service, read from the defaults, the configuration file, the environment variables and the
+//command line flags, each source overrides the previous ones.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub auth: AuthSettings,
+    pub cors: CorsSettings,
+    pub logging: LoggingSettings,
+    pub features: FeatureSettings,
+    pub trash: TrashSettings,
+    pub health: HealthSettings,
+    pub rate_limit: RateLimitSettings,
+    pub ui: UiSettings,
+    pub api: ApiSettings,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub hostname_port: String,
+    //Number of worker threads, the number of physical cores when not set.
+    pub workers: Option<usize>,
+    //0 disables the keep-alive.
+    pub keep_alive_secs: u64,
+    //Time the readiness probe fails after a SIGTERM before the server stops accepting connections, so Kubernetes
+    //stops sending traffic first.
+    pub shutdown_delay_secs: u64,
+    //Deadline of the requests in flight when the server stops, they are aborted after it.
+    pub shutdown_timeout_secs: u64,
+    pub tls: TlsSettings,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            hostname_port: "0.0.0.0:3000".into(),
+            workers: None,
+            keep_alive_secs: 5,
+            shutdown_delay_secs: 10,
+            shutdown_timeout_secs: 30,
+            tls: TlsSettings::default(),
+        }
+    }
+}
+
+//PEM files of the certificate chain and of the private key, the server only accepts HTTPS when enabled.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSettings {
+    pub enabled: bool,
+    pub cert_file: Option<PathBuf>,
+    pub key_file: Option<PathBuf>,
+}
+
+impl TlsSettings {
+    //The settings must have been validated.
+    pub fn ssl_acceptor(&self) -> Result<SslAcceptorBuilder, String> {
+        let (cert_file, key_file) = match (&self.cert_file, &self.key_file) {
+            (Some(cert_file), Some(key_file)) => (cert_file, key_file),
+            _ => return Err("The certificate and the key files are required.".into()),
+        };
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).map_err(|err| err.to_string())?;
+        builder
+            .set_private_key_file(key_file, SslFiletype::PEM)
+            .map_err(|err| format!("Invalid key file {}: {}", key_file.display(), err))?;
+        builder
+            .set_certificate_chain_file(cert_file)
+            .map_err(|err| format!("Invalid certificate file {}: {}", cert_file.display(), err))?;
+        builder.check_private_key().map_err(|_| "The key does not match the certificate.".to_string())?;
+        Ok(builder)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            url: String::new(),
+            max_connections: 20,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    pub disabled: bool,
+    pub jwt_secret: Option<String>,
+    pub jwks_file: Option<PathBuf>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+}
+
+//The level is a tracing filter, a level or a list of target=level, e.g. "info,sqlx=warn".
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSettings {
+    pub level: String,
+    pub format: LogFormat,
+    pub access_log: bool,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        LoggingSettings {
+            level: "info".into(),
+            format: LogFormat::Json,
+            access_log: true,
+        }
+    }
+}
+
+//One JSON object per line for the log collectors, or text lines for the terminal.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Json,
+    Text,
+}
+
+//The disabled features answer 404 Not Found.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureSettings {
+    //POST /library/bulk and POST /library/import.
+    pub bulk_import: bool,
+    //GET /library/export.
+    pub export: bool,
+    //The /admin/api-keys routes and the X-API-Key authentication.
+    pub api_keys: bool,
+    //GET /metrics and the measures of the requests.
+    pub metrics: bool,
+    //GET /openapi.json and the GET /docs page.
+    pub openapi: bool,
+}
+
+impl Default for FeatureSettings {
+    fn default() -> Self {
+        FeatureSettings {
+            bulk_import: true,
+            export: true,
+            api_keys: true,
+            metrics: true,
+            openapi: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashSettings {
+    pub retention_days: i64,
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        TrashSettings {
+            retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+        }
+    }
+}
+
+//Checks of the readiness route, the IPFS Kubo API is only checked when its URL is set.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthSettings {
+    pub timeout_ms: u64,
+    pub ipfs_api_url: Option<String>,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        HealthSettings {
+            timeout_ms: 2000,
+            ipfs_api_url: None,
+        }
+    }
+}
+
+//Token buckets of every client in every route group, the client is the subject of the bearer token or of the
+//API key, else the IP address.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    //Reads the IP address of the client from the Forwarded or X-Forwarded-For header of the proxy.
+    pub trust_forwarded_for: bool,
+    pub read: Quota,
+    pub write: Quota,
+    pub bulk: Quota,
+    pub export: Quota,
+    pub admin: Quota,
+    //Every request of an address, before its authentication.
+    pub ip: Quota,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        RateLimitSettings {
+            enabled: true,
+            trust_forwarded_for: false,
+            read: Quota { burst: 120, per_minute: 600 },
+            write: Quota { burst: 30, per_minute: 120 },
+            bulk: Quota { burst: 2, per_minute: 6 },
+            export: Quota { burst: 2, per_minute: 10 },
+            admin: Quota { burst: 10, per_minute: 60 },
+            ip: Quota { burst: 300, per_minute: 1800 },
+        }
+    }
+}
+
+//A client can send burst requests at once, then per_minute requests a minute.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+//Compiled bundle of library-ui (npm run build), served with a fallback to its index.html. The UI is not served
+//when the directory has no index.html.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiSettings {
+    pub enabled: bool,
+    pub dir: PathBuf,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        UiSettings {
+            enabled: true,
+            dir: PathBuf::from("../library-ui/build"),
+        }
+    }
+}
+
+impl UiSettings {
+    pub fn index_file(&self) -> PathBuf {
+        self.dir.join("index.html")
+    }
+}
+
+//The routes without version (/api/library...) are the deprecated aliases of the /api/v1 routes, they are answered
+//with the Deprecation and Sunset headers until they are removed. The dates are a decision of the operator, they
+//have no default and are required while the legacy routes are served.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiSettings {
+    pub legacy_routes: bool,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            legacy_routes: true,
+            deprecated_at: None,
+            sunset_at: None,
+        }
+    }
+}
+
+impl ApiSettings {
+    //The deprecation and sunset dates of the legacy routes, None when they are not served.
+    pub fn legacy_dates(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        match (self.legacy_routes, self.deprecated_at, self.sunset_at) {
+            (true, Some(deprecated_at), Some(sunset_at)) => Some((deprecated_at, sunset_at)),
+            _ => None,
+        }
+    }
+}
+
+impl Settings {
+    //The configuration file is the given one, else CONFIG_FILE, else library-service.toml/.yaml when it exists.
+    //The overrides are the key=value pairs of the command line flags, e.g. ("server.workers", "4").
+    pub fn load(file: Option<&Path>, overrides: &[(String, String)]) -> Result<Self, String> {
+        Settings::load_from(file, env::vars().collect(), overrides)
+    }
+
+    pub fn load_from(
+        file: Option<&Path>,
+        vars: HashMap<String, String>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, String> {
+        let file = file.map(Path::to_path_buf).or_else(|| vars.get("CONFIG_FILE").map(PathBuf::from));
+        let file = match &file {
+            Some(path) => File::with_name(&path.to_string_lossy()).required(true),
+            None => File::with_name(DEFAULT_CONFIG_NAME).required(false),
+        };
+        //The legacy variables are renamed to the form of the LIBRARY__* variables, without the prefix.
+        let legacy_vars = LEGACY_VARIABLES
+            .iter()
+            .filter_map(|(name, key)| vars.get(*name).map(|value| (key.replace('.', ENV_SEPARATOR), value.clone())))
+            .collect();
+        let mut builder = Config::builder()
+            .add_source(file)
+            .add_source(environment(None).source(Some(legacy_vars)))
+            .add_source(environment(Some(ENV_PREFIX)).source(Some(vars)));
+        for (key, value) in overrides {
+            builder = builder.set_override(key, value.as_str()).map_err(|err| format!("Invalid flag {}: {}", key, err))?;
+        }
+        let mut settings = builder
+            .build()
+            .and_then(Config::try_deserialize::<Settings>)
+            .map_err(|err| err.to_string())?;
+        for list in [
+            &mut settings.cors.allowed_origins,
+            &mut settings.cors.allowed_methods,
+            &mut settings.cors.allowed_headers,
+            &mut settings.cors.exposed_headers,
+        ] {
+            *list = list.iter().map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect();
+        }
+        Ok(settings)
+    }
+
+    //Every invalid value is reported, prefixed with its key.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.server.hostname_port.to_socket_addrs().map_or(true, |mut addrs| addrs.next().is_none()) {
+            errors.push(format!("server.hostname_port must be host:port: {:?}", self.server.hostname_port));
+        }
+        if self.server.workers == Some(0) {
+            errors.push("server.workers must be at least 1.".into());
+        }
+        if self.server.tls.enabled {
+            for (key, path) in [("server.tls.cert_file", &self.server.tls.cert_file), ("server.tls.key_file", &self.server.tls.key_file)] {
+                match path {
+                    Some(path) if path.is_file() => {}
+                    Some(path) => errors.push(format!("{} does not exist: {}", key, path.display())),
+                    None => errors.push(format!("{} is required when the TLS is enabled.", key)),
+                }
+            }
+        }
+        if self.database.url.is_empty() {
+            errors.push("database.url is required (LIBRARY__DATABASE__URL or DATABASE_URL).".into());
+        } else if !self.database.url.starts_with("postgres://") && !self.database.url.starts_with("postgresql://") {
+            errors.push("database.url must be a postgres:// URL.".into());
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1.".into());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            errors.push("database.min_connections must not be greater than database.max_connections.".into());
+        }
+        if self.database.acquire_timeout_secs == 0 {
+            errors.push("database.acquire_timeout_secs must be at least 1.".into());
+        }
+        if !self.auth.disabled && self.auth.jwt_secret.as_deref().unwrap_or("").is_empty() && self.auth.jwks_file.is_none() {
+            errors.push("auth.jwt_secret or auth.jwks_file is required, or auth.disabled = true.".into());
+        }
+        if let Err(err) = self.cors.validate() {
+            errors.push(format!("cors: {}", err));
+        }
+        if !is_log_filter(&self.logging.level) {
+            errors.push(format!("logging.level must be a level ({}) or target=level list: {:?}", LOG_LEVELS.join(", "), self.logging.level));
+        }
+        if !(0..=MAX_TRASH_RETENTION_DAYS).contains(&self.trash.retention_days) {
+            errors.push(format!("trash.retention_days must be between 0 and {}.", MAX_TRASH_RETENTION_DAYS));
+        }
+        for (group, quota) in [
+            ("read", &self.rate_limit.read),
+            ("write", &self.rate_limit.write),
+            ("bulk", &self.rate_limit.bulk),
+            ("export", &self.rate_limit.export),
+            ("admin", &self.rate_limit.admin),
+            ("ip", &self.rate_limit.ip),
+        ] {
+            if quota.burst == 0 || quota.per_minute == 0 {
+                errors.push(format!("rate_limit.{}.burst and rate_limit.{}.per_minute must be at least 1.", group, group));
+            }
+        }
+        if self.health.timeout_ms == 0 {
+            errors.push("health.timeout_ms must be at least 1.".into());
+        }
+        if let Some(url) = &self.health.ipfs_api_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!("health.ipfs_api_url must be a http:// URL: {:?}", url));
+            }
+        }
+        if self.api.legacy_routes {
+            match (self.api.deprecated_at, self.api.sunset_at) {
+                (Some(deprecated_at), Some(sunset_at)) if sunset_at <= deprecated_at => {
+                    errors.push("api.sunset_at must be after api.deprecated_at.".into());
+                }
+                (Some(_), Some(_)) => {}
+                _ => errors.push("api.deprecated_at and api.sunset_at are required, or api.legacy_routes = false.".into()),
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    //Copy of the settings without the password of the database and the JWT secret, for --print-config.
+    pub fn redacted(&self) -> Settings {
+        let mut settings = self.clone();
+        settings.database.url = redact_url(&settings.database.url);
+        if settings.auth.jwt_secret.is_some() {
+            settings.auth.jwt_secret = Some(REDACTED.into());
+        }
+        settings
+    }
+}
+
+fn environment(prefix: Option<&str>) -> Environment {
+    let environment = match prefix {
+        Some(prefix) => Environment::with_prefix(prefix).prefix_separator(ENV_SEPARATOR),
+        None => Environment::default(),
+    }
+    .separator(ENV_SEPARATOR)
+    .try_parsing(true)
+    .ignore_empty(true)
+    .list_separator(",");
+    LIST_KEYS.iter().fold(environment, |environment, key| environment.with_list_parse_key(key))
+}
+
+fn is_log_filter(filter: &str) -> bool {
+    !filter.is_empty()
+        && filter.split(',').all(|directive| {
+            let level = directive.rsplit_once('=').map_or(directive, |(_, level)| level);
+            LOG_LEVELS.contains(&level.trim().to_ascii_lowercase().as_str())
+        })
+}
+
+fn redact_url(url: &str) -> String {
+    let (address, query) = match url.split_once('?') {
+        Some((address, query)) => (address, Some(query)),
+        None => (url, None),
+    };
+    let address = match address.split_once("://").and_then(|(scheme, rest)| {
+        let (userinfo, host) = rest.rsplit_once('@')?;
+        let (user, _password) = userinfo.split_once(':')?;
+        Some(format!("{}://{}:{}@{}", scheme, user, REDACTED, host))
+    }) {
+        Some(redacted) => redacted,
+        None => address.to_string(),
+    };
+    match query {
+        Some(query) => {
+            let parameters: Vec<String> = query
+                .split('&')
+                .map(|parameter| match parameter.split_once('=') {
+                    Some((key, _)) if SECRET_URL_PARAMETERS.contains(&key.to_ascii_lowercase().as_str()) => {
+                        format!("{}={}", key, REDACTED)
+                    }
+                    _ => parameter.to_string(),
+                })
+                .collect();
+            format!("{}?{}", address, parameters.join("&"))
+        }
+        None => address,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use crate::routes::book_routes_with_features;
+    use crate::test_utils::test_state;
+
+    #[actix_rt::test]
+    async fn test_settings() {
+        let config_file = env::temp_dir().join("library-service-test-settings.toml");
+        std::fs::write(&config_file, r#"
+            [server]
+            workers = 2
+            [database]
+            url = "postgres://file:file@db/library"
+            max_connections = 5
+            [features]
+            export = false
+            [api]
+            deprecated_at = "2030-01-01T00:00:00Z"
+            sunset_at = "2030-07-01T00:00:00Z"
+        "#).unwrap();
+        let vars = HashMap::from([
+            ("DATABASE_URL".to_string(), "postgres://legacy:legacy@db/library".to_string()),
+            ("LIBRARY__DATABASE__URL".to_string(), "postgres://admin:s3cret@db:5432/library".to_string()),
+            ("LIBRARY__SERVER__WORKERS".to_string(), "3".to_string()),
+            ("SERVER_HOSTNAME_PORT".to_string(), "127.0.0.1:8080".to_string()),
+            ("CORS_ALLOWED_ORIGINS".to_string(), "https://a.example.com, https://b.example.com".to_string()),
+            ("JWT_SECRET".to_string(), "jwt-s3cret".to_string()),
+            ("LIBRARY_SERVICE_PORT".to_string(), "tcp://10.0.0.1:3000".to_string()),
+        ]);
+        let overrides = [
+            ("server.workers".to_string(), "4".to_string()),
+            ("database.min_connections".to_string(), "2".to_string()),
+        ];
+        let settings = Settings::load_from(Some(&config_file), vars.clone(), &overrides).unwrap();
+        assert_eq!(settings.server.workers, Some(4));
+        assert_eq!(settings.server.hostname_port, "127.0.0.1:8080");
+        assert_eq!(settings.database.url, "postgres://admin:s3cret@db:5432/library");
+        assert_eq!(settings.database.max_connections, 5);
+        assert_eq!(settings.database.min_connections, 2);
+        assert_eq!(settings.database.idle_timeout_secs, 30);
+        assert_eq!(settings.cors.allowed_origins, vec!["https://a.example.com", "https://b.example.com"]);
+        assert!(!settings.features.export);
+        assert!(settings.features.bulk_import);
+        assert_eq!(settings.validate(), Ok(()));
+
+        let printed = toml::to_string_pretty(&settings.redacted()).unwrap();
+        assert!(printed.contains("postgres://admin:***@db:5432/library"));
+        assert!(!printed.contains("s3cret"));
+
+        let yaml_file = env::temp_dir().join("library-service-test-settings.yaml");
+        std::fs::write(&yaml_file, "database:\n  max_conections: 5\n").unwrap();
+        let err = Settings::load_from(Some(&yaml_file), vars, &[]).unwrap_err();
+        assert!(err.contains("max_conections"), "{}", err);
+
+        let overrides = [
+            ("server.workers".to_string(), "0".to_string()),
+            ("database.max_connections".to_string(), "2".to_string()),
+            ("database.min_connections".to_string(), "3".to_string()),
+            ("logging.level".to_string(), "verbose".to_string()),
+            ("trash.retention_days".to_string(), "9223372036854775807".to_string()),
+        ];
+        let errors = Settings::load_from(None, HashMap::new(), &overrides).unwrap().validate().unwrap_err();
+        for key in ["server.workers", "database.url", "database.min_connections", "auth.jwt_secret", "logging.level", "trash.retention_days", "api.deprecated_at"] {
+            assert!(errors.iter().any(|error| error.starts_with(key)), "{} must be reported: {:?}", key, errors);
+        }
+        std::fs::remove_file(config_file).ok();
+        std::fs::remove_file(yaml_file).ok();
+
+        let shared_data = test_state().await;
+        let features = FeatureSettings {
+            export: false,
+            ..FeatureSettings::default()
+        };
+        let app = test::init_service(App::new()
+            .app_data(shared_data)
+            .configure(|cfg| book_routes_with_features(cfg, &features))).await;
+        let request = test::TestRequest::get().uri("/library/export").to_request();
+        let http_response = test::call_service(&app, request).await;
+        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_redact_url() {
+        for (url, redacted) in [
+            ("postgres://admin:s3cret@db:5432/library", "postgres://admin:***@db:5432/library"),
+            ("postgres://admin@db/library", "postgres://admin@db/library"),
+            ("postgres://db/library?user=admin&password=s3cret", "postgres://db/library?user=admin&password=***"),
+            (
+                "postgres://admin:s3cret@db/library?sslmode=require&SSLPASSWORD=k3y",
+                "postgres://admin:***@db/library?sslmode=require&SSLPASSWORD=***",
+            ),
+            ("postgres://db/library?application_name=a@b", "postgres://db/library?application_name=a@b"),
+        ] {
+            assert_eq!(redact_url(url), redacted);
+        }
+    }
+}
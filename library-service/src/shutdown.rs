@@ -0,0 +1,215 @@
+use actix_rt::signal::unix::{signal, Signal, SignalKind};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServerHandle, ServiceRequest, ServiceResponse, Transform};
+use actix_web::web::Bytes;
+use actix_web::{web, Error};
+use futures_util::future::{pending, select, Either, LocalBoxFuture};
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tracing::{info, warn};
+
+//State of the shutdown shared by the workers, registered in the App. The readiness probe fails once the service
+//is draining.
+#[derive(Debug, Default)]
+pub struct ShutdownState {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: AtomicUsize,
+    aborted: AtomicUsize,
+}
+
+//Requests completed and aborted since the shutdown started, the requests still in flight when the server
+//stopped are aborted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrainSummary {
+    pub drained: usize,
+    pub aborted: usize,
+}
+
+impl ShutdownState {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    //Returns the number of requests in flight.
+    pub fn start_draining(&self) -> usize {
+        self.draining.store(true, Ordering::SeqCst);
+        self.in_flight()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    pub fn summary(&self) -> DrainSummary {
+        DrainSummary {
+            drained: self.drained.load(Ordering::SeqCst),
+            aborted: self.aborted.load(Ordering::SeqCst) + self.in_flight(),
+        }
+    }
+}
+
+//Counts a request until it is dropped, the request is aborted when it is dropped before the end of its response.
+struct InFlightGuard {
+    state: web::Data<ShutdownState>,
+    completed: bool,
+}
+
+impl InFlightGuard {
+    fn new(state: web::Data<ShutdownState>) -> Self {
+        state.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard { state, completed: false }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+        if self.state.is_draining() {
+            match self.completed {
+                true => self.state.drained.fetch_add(1, Ordering::SeqCst),
+                false => self.state.aborted.fetch_add(1, Ordering::SeqCst),
+            };
+        }
+    }
+}
+
+//Body of a response which keeps its request in flight until the body is sent, so the streamed responses are
+//drained too. The request is aborted when the body is dropped before its end.
+pub struct InFlightBody<B> {
+    body: Pin<Box<B>>,
+    guard: Option<InFlightGuard>,
+}
+
+impl<B: MessageBody> MessageBody for InFlightBody<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let poll = self.body.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            if let Some(mut guard) = self.guard.take() {
+                guard.completed = true;
+            }
+        }
+        poll
+    }
+}
+
+//Counts the requests in flight in the ShutdownState, the requests are not counted when it is not registered.
+pub struct InFlightRequests;
+
+impl<S, B> Transform<S, ServiceRequest> for InFlightRequests
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<InFlightBody<B>>;
+    type Error = Error;
+    type Transform = InFlightRequestsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(InFlightRequestsMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct InFlightRequestsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for InFlightRequestsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<InFlightBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let mut guard = req.app_data::<web::Data<ShutdownState>>().cloned().map(InFlightGuard::new);
+        let future = self.service.call(req);
+        Box::pin(async move {
+            match future.await {
+                Ok(response) => Ok(response.map_body(|_, body| InFlightBody { body: Box::pin(body), guard })),
+                Err(err) => {
+                    if let Some(guard) = guard.as_mut() {
+                        guard.completed = true;
+                    }
+                    Err(err)
+                }
+            }
+        })
+    }
+}
+
+//SIGTERM is sent by Kubernetes, SIGINT by Ctrl-C.
+struct Signals {
+    terminate: Signal,
+    interrupt: Signal,
+}
+
+impl Signals {
+    fn new() -> std::io::Result<Self> {
+        Ok(Signals {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    async fn recv(&mut self) -> SignalKind {
+        match select(Box::pin(self.terminate.recv()), Box::pin(self.interrupt.recv())).await {
+            Either::Left(_) => SignalKind::terminate(),
+            Either::Right(_) => SignalKind::interrupt(),
+        }
+    }
+}
+
+fn signal_name(signal: SignalKind) -> &'static str {
+    if signal == SignalKind::terminate() { "SIGTERM" } else { "SIGINT" }
+}
+
+//Replaces the signal handling of actix, which stops accepting connections at once while the pod still receives
+//traffic. The server has to be started with the signals disabled. The future only ends on a second signal during
+//the drain, the server is then dropped without waiting for the requests in flight.
+pub async fn shutdown_on_signal(server: ServerHandle, state: web::Data<ShutdownState>, delay: Duration) {
+    let mut signals = match Signals::new() {
+        Ok(signals) => signals,
+        Err(err) => {
+            warn!(error = %err, "failed to listen to the signals, they stop the process without draining");
+            return pending().await;
+        }
+    };
+    let signal = signals.recv().await;
+    let in_flight = state.start_draining();
+    info!(signal = signal_name(signal), in_flight, "shutdown started, the readiness probe fails");
+    let drain = async {
+        //The delay is only needed when the pod is removed from the endpoints of its service.
+        if signal == SignalKind::terminate() && !delay.is_zero() {
+            actix_rt::time::sleep(delay).await;
+        }
+        info!(in_flight = state.in_flight(), "stop accepting connections and drain the requests in flight");
+        server.stop(true).await;
+    };
+    let stop = select(Box::pin(drain), Box::pin(signals.recv())).await;
+    match stop {
+        //The server future ends with the drain.
+        Either::Left(_) => pending().await,
+        Either::Right((signal, _)) => {
+            warn!(signal = signal_name(signal), in_flight = state.in_flight(), "second signal, stop without draining");
+        }
+    }
+}
@@ -3,4 +3,6 @@ use sqlx::postgres::PgPool;
 pub struct AppState {
     pub probe: String,
     pub db: PgPool,
+    //Days a deleted book stays in the trash before it can be purged.
+    pub trash_retention_days: i64,
 }
\ No newline at end of file
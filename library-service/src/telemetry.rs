@@ -0,0 +1,132 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+use crate::settings::{LogFormat, LoggingSettings};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const MAX_REQUEST_ID_LENGTH: usize = 140;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+//Id of the request, the X-Request-Id header of the client or a generated UUID, added to the request extensions
+//by the RequestTracing middleware.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn is_valid(id: &str) -> bool {
+        !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH && id.bytes().all(|byte| byte.is_ascii_graphic())
+    }
+}
+
+//Id of the request being handled, None outside of the RequestTracing middleware.
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(String::clone).ok()
+}
+
+//The settings must have been validated.
+pub fn init_logging(settings: &LoggingSettings) {
+    let subscriber = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&settings.level));
+    match settings.format {
+        LogFormat::Json => subscriber.json().init(),
+        LogFormat::Text => subscriber.init(),
+    }
+}
+
+//Opens a span with the id of every request, sends the id back in the X-Request-Id header and logs the
+//completed requests when the access log is enabled.
+pub struct RequestTracing {
+    pub access_log: bool,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware {
+            service: Rc::new(service),
+            access_log: self.access_log,
+        }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: Rc<S>,
+    access_log: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let access_log = self.access_log;
+        let start = Instant::now();
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| RequestId::is_valid(id))
+            .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+        req.extensions_mut().insert(RequestId(request_id.clone()));
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.path(),
+            route = Empty,
+            status = Empty,
+        );
+        //The handlers turn their errors into responses inside the scope, so the problem details have the id.
+        let future = CURRENT_REQUEST_ID.scope(request_id.clone(), service.call(req));
+        Box::pin(
+            async move {
+                let mut response = future.await?;
+                let span = tracing::Span::current();
+                span.record("route", response.request().match_pattern().as_deref().unwrap_or("unmatched"));
+                span.record("status", response.status().as_u16());
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                if access_log {
+                    let latency_ms = start.elapsed().as_millis() as u64;
+                    if response.status().is_server_error() {
+                        tracing::warn!(latency_ms, "request failed");
+                    } else {
+                        tracing::info!(latency_ms, "request completed");
+                    }
+                }
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,27 @@
+use actix_web::web;
+use dotenv::dotenv;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::env;
+use crate::model::book::DEFAULT_TRASH_RETENTION_DAYS;
+use crate::state::AppState;
+
+pub fn app_state(db: PgPool) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        probe: "Probe test ok....".to_string(),
+        db,
+        trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+    })
+}
+
+//State of the tests, connected to the database of the DATABASE_URL of the .env file.
+pub async fn test_state() -> web::Data<AppState> {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL")
+        .expect("DATABASE_URL is not set in .env file");
+    let db_pool = PgPoolOptions::new()
+        .idle_timeout(std::time::Duration::from_secs(10))
+        .connect(&database_url)
+        .await
+        .unwrap();
+    app_state(db_pool)
+}
@@ -0,0 +1,155 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::guard::{Guard, GuardContext};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::Error;
+use chrono::{DateTime, Utc};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use crate::routes::API_PREFIX;
+
+//Parameter of the media types of the Accept header which asks for a version, e.g. application/json; version=1.
+pub const VERSION_PARAMETER: &str = "version";
+
+//Versions of the API, each one is served under /api/v<number>. A new version is added to ALL and given its
+//routes in routes::version_routes, the previous ones are kept side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 1] = [ApiVersion::V1];
+    //Version of the deprecated routes without version.
+    pub const LEGACY: ApiVersion = ApiVersion::V1;
+
+    pub fn number(self) -> u32 {
+        match self {
+            ApiVersion::V1 => 1,
+        }
+    }
+
+    pub fn prefix(self) -> String {
+        format!("/v{}", self.number())
+    }
+
+    //"1" or "v1".
+    pub fn parse(version: &str) -> Option<ApiVersion> {
+        let number = version.strip_prefix(['v', 'V']).unwrap_or(version).parse::<u32>().ok()?;
+        ApiVersion::ALL.into_iter().find(|version| version.number() == number)
+    }
+}
+
+//The version parameter of the first media type of Accept which has one, Err with its value when it names no
+//version of the API.
+pub fn requested_version(headers: &HeaderMap) -> Option<Result<ApiVersion, String>> {
+    headers
+        .get_all(header::ACCEPT)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .flat_map(|media_type| media_type.split(';').skip(1))
+        .find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.trim().eq_ignore_ascii_case(VERSION_PARAMETER).then(|| value.trim().trim_matches('"'))
+        })
+        .map(|value| ApiVersion::parse(value).ok_or_else(|| value.to_string()))
+}
+
+//Matches the requests which ask for the version in Accept, and the requests without version when it is the
+//default version of the scope.
+pub struct AcceptVersion {
+    pub version: ApiVersion,
+    pub default: bool,
+}
+
+impl Guard for AcceptVersion {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        match requested_version(ctx.head().headers()) {
+            None => self.default,
+            Some(Ok(version)) => version == self.version,
+            Some(Err(_)) => false,
+        }
+    }
+}
+
+//Adds the Deprecation (RFC 9745) and Sunset (RFC 8594) headers to the responses of the routes without version,
+//with a link to the same route of their version.
+pub struct Deprecated {
+    version: ApiVersion,
+    deprecation: HeaderValue,
+    sunset: HeaderValue,
+}
+
+impl Deprecated {
+    pub fn new(version: ApiVersion, deprecated_at: DateTime<Utc>, sunset_at: DateTime<Utc>) -> Self {
+        Deprecated {
+            version,
+            deprecation: HeaderValue::from_str(&format!("@{}", deprecated_at.timestamp())).unwrap(),
+            sunset: HeaderValue::from_str(&sunset_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).unwrap(),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Deprecated
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeprecatedMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeprecatedMiddleware {
+            service: Rc::new(service),
+            version: self.version,
+            deprecation: self.deprecation.clone(),
+            sunset: self.sunset.clone(),
+        }))
+    }
+}
+
+pub struct DeprecatedMiddleware<S> {
+    service: Rc<S>,
+    version: ApiVersion,
+    deprecation: HeaderValue,
+    sunset: HeaderValue,
+}
+
+impl<S, B> Service<ServiceRequest> for DeprecatedMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let successor = req.path().strip_prefix(API_PREFIX).map(|path| {
+            format!("<{}{}{}>; rel=\"successor-version\"", API_PREFIX, self.version.prefix(), path)
+        });
+        let deprecation = self.deprecation.clone();
+        let sunset = self.sunset.clone();
+        let future = self.service.call(req);
+        Box::pin(async move {
+            let mut response = future.await?;
+            let headers = response.headers_mut();
+            headers.insert(HeaderName::from_static("deprecation"), deprecation);
+            headers.insert(HeaderName::from_static("sunset"), sunset);
+            if let Some(link) = successor.and_then(|link| HeaderValue::from_str(&link).ok()) {
+                headers.append(header::LINK, link);
+            }
+            //The version of the response depends on the Accept header.
+            headers.append(header::VARY, HeaderValue::from_static("accept"));
+            Ok(response)
+        })
+    }
+}
+
@@ -9,12 +9,16 @@ const router = createBrowserRouter([
   {
     path: '/',
     element: <LibraryPage />,
-    loader: async () => {
-      const existingData = queryClient.getQueryData(['libraryData']);
+    loader: async ({ request }) => {
+      const cursor = new URL(request.url).searchParams.get('cursor');
+      const queryKey = ['libraryData', cursor];
+      const existingData = queryClient.getQueryData(queryKey);
       if (existingData) {
-        return defer({ posts: existingData });
+        return defer({ page: existingData });
       }
-      return defer({ posts: queryClient.fetchQuery(['libraryData'], getBooks) });
+      return defer({
+        page: queryClient.fetchQuery(queryKey, () => getBooks(cursor === null ? null : Number(cursor))),
+      });
     },
   },
 ]);
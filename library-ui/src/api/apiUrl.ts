@@ -0,0 +1,4 @@
+//The bundle served by the library-service calls its API on the same origin, REACT_APP_API_URL points the development server to it.
+export function apiUrl(path: string): string {
+  return (process.env.REACT_APP_API_URL ?? '/api/v1/').concat(path);
+}
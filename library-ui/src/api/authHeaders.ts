@@ -0,0 +1,5 @@
+//The bundle is public, it only holds an API key with the reader scope, so the UI can read the books but not change them.
+export function authHeaders(): Record<string, string> {
+  const apiKey = process.env.REACT_APP_API_KEY;
+  return apiKey ? { 'X-API-Key': apiKey } : {};
+}
@@ -1,15 +1,54 @@
 import { Book } from '../types/Book';
+import { apiUrl } from './apiUrl';
+import { authHeaders } from './authHeaders';
 
-export async function getBooks() {
-  const response = await fetch(process.env.REACT_APP_API_URL!.concat('library'), {
+export type BookPage = {
+  items: Book[];
+  total: number;
+  next_cursor: number | null;
+};
+
+const PAGE_LIMIT = 50;
+
+//One page of books, the next page is read with the next_cursor of this one.
+export async function getBooks(cursor: number | null): Promise<BookPage> {
+  const query = cursor === null ? '' : `&cursor=${cursor}`;
+  const response = await fetch(apiUrl(`library?limit=${PAGE_LIMIT}${query}`), {
     method: 'GET',
     headers: {
       'Content-Type': 'application/json',
+      ...authHeaders(),
     },
   });
-  const body = (await response.json()) as unknown;
-  assertIsBooks(body);
-  return body;
+  const body = (await response.json().catch(() => null)) as unknown;
+  if (!response.ok) {
+    throw new Error(problemMessage(body));
+  }
+  assertIsBookPage(body);
+  assertIsBooks(body.items);
+  return { items: body.items, total: body.total, next_cursor: body.next_cursor };
+}
+
+//The library-service returns its errors as problem details.
+function problemMessage(problem: any): string {
+  return problem?.detail ?? 'The books could not be read';
+}
+
+export function assertIsBookPage(
+  page: unknown,
+): asserts page is { items: unknown; total: number; next_cursor: number | null } {
+  if (typeof page !== 'object' || page === null) {
+    throw new Error("Response isn't an object");
+  }
+  if (!('items' in page)) {
+    throw new Error("Response doesn't contain items");
+  }
+  if (!('total' in page)) {
+    throw new Error("Response doesn't contain total");
+  }
+  if (!('next_cursor' in page)) {
+    throw new Error("Response doesn't contain next_cursor");
+  }
 }
 
 export function assertIsBooks(items: unknown): asserts items is Book[] {
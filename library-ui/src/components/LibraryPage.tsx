@@ -1,14 +1,10 @@
 import { Suspense } from 'react';
-import { useLoaderData, Await, useNavigate } from 'react-router-dom';
-import { useMutation, useQueryClient } from '@tanstack/react-query';
-import { assertIsBooks } from '../api/getBooks';
-import { Book } from '../types/Book';
+import { useLoaderData, Await, Link, useAsyncError } from 'react-router-dom';
+import { assertIsBookPage, assertIsBooks, BookPage } from '../api/getBooks';
 import { BooksList } from './BooksList';
-import { addBook } from '../api/addBook';
-import { NewBookForm } from './NewBookForm';
 
 type Data = {
-  posts: Book[];
+  page: BookPage;
 };
 
 export function assertIsData(data: unknown): asserts data is Data {
@@ -18,39 +14,51 @@ export function assertIsData(data: unknown): asserts data is Data {
   if (data === null) {
     throw new Error('Data is null');
   }
-  if (!('posts' in data)) {
-    throw new Error("data doesn't contain posts");
+  if (!('page' in data)) {
+    throw new Error("data doesn't contain page");
   }
 }
 
-export function LibraryPage() {
-  const navigate = useNavigate();
-  const queryClient = useQueryClient();
-  const { mutate } = useMutation(addBook, {
-    onSuccess: (addBook) => {
-      queryClient.setQueryData<Book[]>(['libraryData'], (oldData) => {
-        if (oldData === undefined) {
-          return [addBook];
-        } else {
-          return [addBook, ...oldData];
-        }
-      });
-      navigate('/');
-    },
-  });
+function PageError() {
+  const error = useAsyncError();
+  return (
+    <div className="text-red-500 mt-4">
+      {error instanceof Error ? error.message : 'The books could not be read'}{' '}
+      <Link to="/" className="underline">
+        Back to the first page
+      </Link>
+    </div>
+  );
+}
 
+export function LibraryPage() {
   const data = useLoaderData();
   assertIsData(data);
 
   return (
     <div className="w-96 mx-auto mt-6">
       <h2 className="text-xl text-slate-900 font-bold">Library</h2>
-      <NewBookForm onSave={mutate} />
       <Suspense fallback={<div>Fetching...</div>}>
-        <Await resolve={data.posts}>
-          {(books) => {
-            assertIsBooks(books);
-            return <BooksList books={books} />;
+        <Await resolve={data.page} errorElement={<PageError />}>
+          {(page) => {
+            assertIsBookPage(page);
+            assertIsBooks(page.items);
+            return (
+              <>
+                <BooksList books={page.items} />
+                <nav className="flex justify-between py-4">
+                  <Link to="/" className="underline">
+                    First page
+                  </Link>
+                  <span className="text-slate-500">{page.total} books</span>
+                  {page.next_cursor !== null && (
+                    <Link to={`/?cursor=${page.next_cursor}`} className="underline">
+                      Next page
+                    </Link>
+                  )}
+                </nav>
+              </>
+            );
           }}
         </Await>
       </Suspense>